@@ -1,11 +1,32 @@
 extern crate prost_build;
 
+use std::fs;
+
 fn main() {
-    let protos = [
-        "./sgcp/sgcp.proto",
-        "./sgcp/bms.proto",
-        "./sgcp/emg.proto",
-        "./sgcp/maestro.proto",
-    ];
-    prost_build::compile_protos(&protos, &["./sgcp"]).unwrap();
+    // Compiles every SGCP definition, so a new resource's proto needs no changes here
+    let mut protos: Vec<_> = fs::read_dir("./sgcp")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "proto")
+        })
+        .collect();
+    protos.sort();
+    println!("cargo:rerun-if-changed=./sgcp");
+
+    prost_build::Config::new()
+        // Nests the generated code by package, `sgcp.maestro` becomes `sgcp::maestro`. Included
+        // by `import_sgcp!`.
+        .include_file("_includes.rs")
+        // Lets the HTTP dispatcher accept JSON encoded requests. Missing fields take their
+        // protobuf defaults and task data is keyed by field name, i.e. {"maestro_data": {..}}
+        .type_attribute(".", "#[derive(serde::Deserialize)]")
+        .message_attribute(".", "#[serde(default)]")
+        .type_attribute(
+            ".sgcp.Request.task_data",
+            "#[serde(rename_all = \"snake_case\")]",
+        )
+        .compile_protos(&protos, &["./sgcp"])
+        .unwrap();
 }
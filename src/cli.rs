@@ -0,0 +1,189 @@
+// Command line arguments. Kept deliberately small, anything more involved belongs in the config
+// file.
+use crate::config;
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
+use anyhow::Error;
+use anyhow::Result;
+use log::LevelFilter;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: gpm [OPTIONS]
+       gpm check-config [PATH] [OPTIONS]
+       gpm dump-config [PATH] [OPTIONS]
+
+Commands:
+  check-config              Parse and validate the config, then exit
+  dump-config               Print the effective config with every default filled in, then exit
+
+Options:
+  --config <PATH>           Config file to load (default: $GPM_CONFIG, then the build's default)
+  --strategy <STRATEGIES>   Comma separated dispatch strategies, overriding the config file
+  --log-level <LEVEL>       off, error, warn, info, debug or trace, for every module, overriding
+                            the config and RUST_LOG
+  -h, --help                Print this message
+
+Any config value can also be overridden with a GPM_<PATH> environment variable, where nested
+keys are separated by a double underscore, i.e. GPM_DISPATCHER__TCP__ADDRESS=0.0.0.0:4760";
+
+/// What the binary was asked to do. Only `Run` starts the managers and dispatchers.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Command {
+    #[default]
+    Run,
+    CheckConfig,
+    DumpConfig,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    pub command: Command,
+    pub config: Option<PathBuf>,
+    pub strategy: Option<Vec<CommandDispatchStrategy>>,
+    pub log_level: Option<LevelFilter>,
+    pub help: bool,
+}
+
+impl Args {
+    /// Parses the arguments following the program name. Accepts both `--flag value` and
+    /// `--flag=value`, and an optional leading command which may be followed by the config path.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().peekable();
+        parsed.command = match args.peek().map(String::as_str) {
+            Some("check-config") => Command::CheckConfig,
+            Some("dump-config") => Command::DumpConfig,
+            _ => Command::Run,
+        };
+        if parsed.command != Command::Run {
+            args.next();
+            if let Some(path) = args.next_if(|arg| !arg.starts_with('-')) {
+                parsed.config = Some(PathBuf::from(path));
+            }
+        }
+
+        while let Some(arg) = args.next() {
+            let (flag, mut inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if flag == "-h" || flag == "--help" {
+                parsed.help = true;
+                continue;
+            }
+
+            let mut value = || {
+                inline_value
+                    .take()
+                    .or_else(|| args.next())
+                    .ok_or(Error::msg(format!("Missing value for {}", flag)))
+            };
+            match flag.as_str() {
+                "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                "--strategy" => {
+                    parsed.strategy = Some(
+                        value()?
+                            .split(',')
+                            .map(|strategy| parse_strategy(strategy.trim()))
+                            .collect::<Result<_>>()?,
+                    )
+                },
+                "--log-level" => {
+                    let value = value()?;
+                    parsed.log_level = Some(
+                        LevelFilter::from_str(&value)
+                            .map_err(|_| Error::msg(format!("Invalid log level {:?}", value)))?,
+                    )
+                },
+                _ => return Err(Error::msg(format!("Unknown argument {:?}", flag))),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Loads and validates the config the same way a normal start would, returning a summary
+pub fn check_config(args: &Args) -> Result<String> {
+    let config = Config::load(args)?;
+    Ok(format!(
+        "{:?} is valid; dispatch strategies={:?}",
+        config::base_path(args),
+        config.command_dispatch_strategy
+    ))
+}
+
+/// Loads and validates the config, returning the effective config as TOML
+pub fn dump_config(args: &Args) -> Result<String> {
+    let config = Config::load(args)?;
+    Ok(toml::to_string_pretty(&config)?)
+}
+
+fn parse_strategy(strategy: &str) -> Result<CommandDispatchStrategy> {
+    toml::Value::String(strategy.to_string())
+        .try_into()
+        .map_err(|_| Error::msg(format!("Unknown dispatch strategy {:?}", strategy)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let args = parse(&[
+            "--config",
+            "/etc/gpm.toml",
+            "--strategy=tcp,http",
+            "--log-level",
+            "warn",
+        ])
+        .unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("/etc/gpm.toml")));
+        assert_eq!(
+            args.strategy,
+            Some(vec![
+                CommandDispatchStrategy::Tcp,
+                CommandDispatchStrategy::Http
+            ])
+        );
+        assert_eq!(args.log_level, Some(LevelFilter::Warn));
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse(&[]).unwrap().command, Command::Run);
+
+        let args = parse(&["check-config", "/etc/gpm.toml", "--strategy", "tcp"]).unwrap();
+        assert_eq!(args.command, Command::CheckConfig);
+        assert_eq!(args.config, Some(PathBuf::from("/etc/gpm.toml")));
+
+        let args = parse(&["dump-config", "--log-level", "warn"]).unwrap();
+        assert_eq!(args.command, Command::DumpConfig);
+        assert_eq!(args.config, None);
+    }
+
+    #[test]
+    fn dumped_config_loads_back() {
+        // The config GPM is built for, the dev config isn't valid on the Pi
+        let args = parse(&["dump-config", config::get_config_path()]).unwrap();
+        let dumped: Config = toml::from_str(&dump_config(&args).unwrap()).unwrap();
+        assert_eq!(
+            dumped.command_dispatch_strategy,
+            Config::load(&args).unwrap().command_dispatch_strategy
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse(&["--strategy", "carrier_pigeon"]).is_err());
+        assert!(parse(&["--log-level", "loud"]).is_err());
+        assert!(parse(&["--config"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+}
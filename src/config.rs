@@ -1,12 +1,34 @@
+mod diff;
+mod logging;
+mod validate;
+
+use crate::cli::Args;
+use crate::status::SystemState;
+use anyhow::Error;
+use anyhow::Result;
 use log::LevelFilter;
 use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
 use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::time::Duration;
+use toml::Table;
+use toml::Value;
+
+pub use logging::logger_init;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum CommandDispatchStrategy {
     Tcp,
+    Http,
     Gpio,
     Emg,
 }
@@ -17,14 +39,71 @@ impl Default for CommandDispatchStrategy {
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// Accepts either a single strategy or a list of strategies
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<CommandDispatchStrategy>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(CommandDispatchStrategy),
+        Many(Vec<CommandDispatchStrategy>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(strategy) => vec![strategy],
+        OneOrMany::Many(strategies) => strategies,
+    })
+}
+
+fn default_dispatch_strategies() -> Vec<CommandDispatchStrategy> {
+    vec![CommandDispatchStrategy::default()]
+}
+
+/// Decides which dispatcher wins when several of them issue Maestro commands
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArbitrationConfig {
+    /// Dispatch strategies from highest to lowest priority. Strategies that are not listed rank
+    /// below every listed one.
+    #[serde(default = "default_arbitration_priority")]
+    pub priority: Vec<CommandDispatchStrategy>,
+    /// How long a Maestro command locks out lower priority dispatchers
+    #[serde(default = "default_override_timeout_ms")]
+    pub override_timeout_ms: u64,
+}
+
+impl Default for ArbitrationConfig {
+    fn default() -> Self {
+        ArbitrationConfig {
+            priority: default_arbitration_priority(),
+            override_timeout_ms: default_override_timeout_ms(),
+        }
+    }
+}
+
+fn default_arbitration_priority() -> Vec<CommandDispatchStrategy> {
+    vec![
+        CommandDispatchStrategy::Tcp,
+        CommandDispatchStrategy::Http,
+        CommandDispatchStrategy::Gpio,
+        CommandDispatchStrategy::Emg,
+    ]
+}
+
+fn default_override_timeout_ms() -> u64 {
+    5000
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Dispatcher {
     pub tcp: ServerConfig,
+    pub http: Option<HttpServerConfig>,
     pub emg: Option<EmgConfig>,
     pub gpio_monitor: Option<GpioMonitorConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub max_concurrent_connections: i32,
     pub address: String,
@@ -32,34 +111,614 @@ pub struct ServerConfig {
     pub frame_prefix_length_in_bytes: i32,
 }
 
-#[derive(Debug, Deserialize)]
+/// HTTP/JSON control API, see `dispatchers::http`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HttpServerConfig {
+    pub address: String,
+    /// Requests with a larger body are rejected
+    #[serde(default = "default_max_body_size_in_bytes")]
+    pub max_body_size_in_bytes: usize,
+}
+
+fn default_max_body_size_in_bytes() -> usize {
+    64 * 1024
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GpioMonitorConfig {
+    /// Edges closer together than this are treated as contact bounce
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Minimum hold time of a long press
+    #[serde(default = "default_long_press_ms")]
+    pub long_press_ms: u64,
+    /// Maximum time between the release of a press and the start of the next one for the two to
+    /// count as a double press
+    #[serde(default = "default_double_press_window_ms")]
+    pub double_press_window_ms: u64,
+    pub buttons: Vec<ButtonConfig>,
+    /// Unix socket accepting "<pin> <high|low>" lines to drive the simulated pins used outside the
+    /// Pi
+    #[cfg_attr(feature = "pi", allow(dead_code))]
+    pub simulator_socket: Option<String>,
+}
+
+/// A push button wired between a GPIO pin and ground
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ButtonConfig {
     pub pin: u8,
+    pub bindings: Vec<ButtonBinding>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PressPattern {
+    Short,
+    Long,
+    Double,
 }
 
-#[derive(Debug, Deserialize)]
+/// SGCP request sent when a button is pressed in a given pattern
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ButtonBinding {
+    pub press: PressPattern,
+    /// SGCP resource name, i.e. "MAESTRO"
+    pub resource: String,
+    pub task_code: String,
+}
+
+fn default_debounce_ms() -> u64 {
+    30
+}
+
+fn default_long_press_ms() -> u64 {
+    800
+}
+
+fn default_double_press_window_ms() -> u64 {
+    300
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct EmgConfig {
     pub buffer_size: usize,
     pub pause_duration_ms: u64,
     pub sampling_speed_ms: u64,
     pub cs_pin: u8,
+    #[serde(default)]
+    pub adc: AdcConfig,
+    /// Electrodes sampled by the EMG manager, in classification order
+    #[serde(default = "default_electrodes")]
+    pub channels: Vec<ElectrodeConfig>,
+    /// Weight of the newest reading in the moving average used as each electrode's envelope
+    #[serde(default = "default_envelope_smoothing")]
+    pub envelope_smoothing: f32,
+}
+
+impl EmgConfig {
+    /// Returns the electrodes that should be sampled
+    pub fn enabled_channels(&self) -> impl Iterator<Item = &ElectrodeConfig> {
+        self.channels.iter().filter(|electrode| electrode.enabled)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdcKind {
+    /// 10-bit, 8 channel SPI ADC
+    #[default]
+    Mcp3008,
+    /// 12-bit, 8 channel SPI ADC
+    Mcp3208,
+    /// 16-bit, 4 channel I2C ADC
+    Ads1115,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdcConfig {
+    #[serde(default)]
+    pub kind: AdcKind,
+    /// SPI bus number, i.e. 0 for /dev/spidev0.x (SPI parts only)
+    #[serde(default)]
+    pub spi_bus: u8,
+    /// SPI slave select line, i.e. 0 for /dev/spidevx.0 (SPI parts only)
+    #[serde(default)]
+    pub slave_select: u8,
+    /// SPI clock speed (SPI parts only)
+    #[serde(default = "default_clock_speed_hz")]
+    pub clock_speed_hz: u32,
+    /// I2C bus number, i.e. 1 for /dev/i2c-1 (I2C parts only)
+    #[serde(default = "default_i2c_bus")]
+    pub i2c_bus: u8,
+    /// 7-bit I2C address of the ADC (I2C parts only)
+    #[serde(default = "default_i2c_address")]
+    pub i2c_address: u16,
+    /// Voltage corresponding to a full scale reading
+    #[serde(default = "default_reference_voltage")]
+    pub reference_voltage: f32,
+}
+
+impl Default for AdcConfig {
+    fn default() -> Self {
+        AdcConfig {
+            kind: AdcKind::default(),
+            spi_bus: 0,
+            slave_select: 0,
+            clock_speed_hz: default_clock_speed_hz(),
+            i2c_bus: default_i2c_bus(),
+            i2c_address: default_i2c_address(),
+            reference_voltage: default_reference_voltage(),
+        }
+    }
+}
+
+fn default_clock_speed_hz() -> u32 {
+    500_000
 }
 
-#[derive(Debug, Deserialize)]
+fn default_i2c_bus() -> u8 {
+    1
+}
+
+fn default_i2c_address() -> u16 {
+    0x48
+}
+
+fn default_reference_voltage() -> f32 {
+    3.3
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ElectrodeConfig {
+    /// ADC channel the electrode is wired to
+    pub channel: u8,
+    /// When set, the electrode is read differentially against this ADC channel
+    #[serde(default)]
+    pub reference_channel: Option<u8>,
+    pub label: String,
+    /// Multiplier applied to raw ADC readings before calibration and classification
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_envelope_smoothing() -> f32 {
+    0.2
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Matches the original two electrode setup (inner forearm on CH0, outer forearm on CH1)
+fn default_electrodes() -> Vec<ElectrodeConfig> {
+    vec![
+        ElectrodeConfig {
+            channel: 0,
+            reference_channel: None,
+            label: "inner".to_string(),
+            gain: default_gain(),
+            enabled: true,
+        },
+        ElectrodeConfig {
+            channel: 1,
+            reference_channel: None,
+            label: "outer".to_string(),
+            gain: default_gain(),
+            enabled: true,
+        },
+    ]
+}
+
+/// Battery voltage monitoring, see `managers::bms`. The battery is read through a voltage divider
+/// on one channel of its own ADC.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BmsConfig {
+    pub cs_pin: u8,
+    #[serde(default)]
+    pub adc: AdcConfig,
+    pub channel: u8,
+    /// Battery voltage divided by the voltage at the ADC input
+    #[serde(default = "default_divider_ratio")]
+    pub divider_ratio: f32,
+    /// Battery voltage below which the arm shows `low_battery`
+    pub low_voltage: f32,
+    /// Battery voltage the battery has to recover to before `low_battery` clears. Defaults to
+    /// `low_voltage`.
+    pub recovery_voltage: Option<f32>,
+    #[serde(default = "default_bms_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+impl BmsConfig {
+    pub fn recovery_voltage(&self) -> f32 {
+        self.recovery_voltage.unwrap_or(self.low_voltage)
+    }
+}
+
+fn default_divider_ratio() -> f32 {
+    1.0
+}
+
+fn default_bms_poll_interval_ms() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TelemetryConfig {
     pub address: String,
     pub tick_interval_in_seconds: i32,
+    #[serde(default)]
+    pub stream: StreamConfig,
+    pub push: Option<PushConfig>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Config {
+/// Pushes metric snapshots to a remote collector, i.e. a Prometheus pushgateway, spooling them on
+/// disk while it can't be reached
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushConfig {
+    /// Plain HTTP endpoint snapshots are POSTed to, i.e.
+    /// "http://grm.local:9091/metrics/job/gpm"
+    pub url: String,
+    #[serde(default = "default_push_interval_in_seconds")]
+    pub interval_in_seconds: u64,
+    pub spool_dir: String,
+    /// The oldest snapshots are dropped once the spool grows past this size
+    #[serde(default = "default_max_spool_bytes")]
+    pub max_spool_bytes: u64,
+    #[serde(default = "default_push_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+}
+
+fn default_push_interval_in_seconds() -> u64 {
+    30
+}
+
+fn default_max_spool_bytes() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_push_request_timeout_ms() -> u64 {
+    5000
+}
+
+/// WebSocket live stream served at `/stream` by the telemetry server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamConfig {
+    /// Frames per second sent to each client, clients may ask for fewer
+    #[serde(default = "default_stream_rate_hz")]
+    pub rate_hz: u32,
+    #[serde(default = "default_stream_max_clients")]
+    pub max_clients: usize,
+    /// Clients that take longer than this to accept a frame are disconnected
+    #[serde(default = "default_stream_send_timeout_ms")]
+    pub send_timeout_ms: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            rate_hz: default_stream_rate_hz(),
+            max_clients: default_stream_max_clients(),
+            send_timeout_ms: default_stream_send_timeout_ms(),
+        }
+    }
+}
+
+fn default_stream_rate_hz() -> u32 {
+    10
+}
+
+fn default_stream_max_clients() -> usize {
+    4
+}
+
+fn default_stream_send_timeout_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum EstopAction {
+    /// Hold every servo at its current position
+    Freeze,
+    /// Stop driving the servos so they can be moved by hand
+    Release,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EstopConfig {
+    /// Pin of a normally open e-stop button wired to ground
+    pub pin: Option<u8>,
+    #[serde(default = "default_estop_action")]
+    pub action: EstopAction,
+}
+
+impl Default for EstopConfig {
+    fn default() -> Self {
+        EstopConfig {
+            pin: None,
+            action: default_estop_action(),
+        }
+    }
+}
+
+fn default_estop_action() -> EstopAction {
+    EstopAction::Freeze
+}
+
+/// Settings for one resource manager, i.e. `[managers.emg]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagerConfig {
+    /// Time a dispatched task may take before the dispatcher gives up waiting for it
+    #[serde(default = "default_deadline_ms")]
+    pub deadline_ms: u64,
+    /// Normal priority tasks that can wait in the manager's queue
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
+    /// What happens to a normal priority task sent to a full queue
+    #[serde(default)]
+    pub backpressure: BackpressurePolicy,
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        ManagerConfig {
+            deadline_ms: default_deadline_ms(),
+            buffer_size: default_buffer_size(),
+            backpressure: BackpressurePolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressurePolicy {
+    /// The sender waits for space, up to the task's deadline
+    #[default]
+    Block,
+    /// The task fails right away with `queue_full`
+    Reject,
+    /// The oldest waiting task is dropped to make room, its sender sees no response
+    DropOldest,
+}
+
+fn default_buffer_size() -> usize {
+    32
+}
+
+/// What happens on SIGTERM or SIGINT, see `dispatchers::shutdown`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShutdownConfig {
+    /// Maestro task moving the hand to a safe pose before exiting. Skipped while the e-stop is
+    /// engaged.
+    #[serde(default = "default_rest_task")]
+    pub rest_task: Option<String>,
+    /// Time allowed for in-flight tasks and the rest pose before exiting regardless
+    #[serde(default = "default_shutdown_deadline_ms")]
+    pub deadline_ms: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            rest_task: default_rest_task(),
+            deadline_ms: default_shutdown_deadline_ms(),
+        }
+    }
+}
+
+fn default_rest_task() -> Option<String> {
+    Some("OPEN_FIST".to_string())
+}
+
+fn default_shutdown_deadline_ms() -> u64 {
+    5000
+}
+
+fn default_deadline_ms() -> u64 {
+    10_000
+}
+
+/// Restarts of resource managers that exit or panic, see `managers::supervisor`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SupervisorConfig {
+    /// Delay before the first restart, doubled for every restart after it
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Cap on the delay. A manager that stays up this long starts over from `initial_backoff_ms`.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        SupervisorConfig {
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusConfig {
+    pub indicators: Vec<IndicatorConfig>,
+}
+
+/// An LED or buzzer on a GPIO output pin
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndicatorConfig {
+    pub name: String,
+    pub pin: u8,
+    /// Pattern played in each system state, the indicator is off in states without one
+    #[serde(default)]
+    pub patterns: HashMap<SystemState, IndicatorPattern>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndicatorPattern {
+    /// Alternating on and off durations, starting with on
+    pub steps_ms: Vec<u64>,
+    /// Loop the pattern for as long as the state lasts, otherwise play it once
+    #[serde(default = "default_repeat")]
+    pub repeat: bool,
+}
+
+fn default_repeat() -> bool {
+    true
+}
+
+/// Log output, see `logger_init`. `RUST_LOG` and `--log-level` take precedence over this.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: LevelFilter,
+    /// Per-module levels overriding `level`, i.e. `{ "gpm::telemetry" = "warn", hyper = "off" }`
     #[serde(default)]
-    pub command_dispatch_strategy: CommandDispatchStrategy,
+    pub filters: HashMap<String, LevelFilter>,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Prints the ASCII banner on startup. Turn off when logs end up in journald.
+    #[serde(default = "default_banner")]
+    pub banner: bool,
+    /// Also writes logs to this file, rotating it as it fills up
+    pub file: Option<RotatingFileConfig>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: default_log_level(),
+            filters: HashMap::new(),
+            format: LogFormat::default(),
+            banner: default_banner(),
+            file: None,
+        }
+    }
+}
+
+fn default_log_level() -> LevelFilter {
+    LevelFilter::Trace
+}
+
+fn default_banner() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// env_logger's human readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with key-value fields such as `resource` and `task` flattened
+    /// into it
+    Json,
+}
+
+/// A file that is rotated to `<name>.1.<ext>`, `<name>.2.<ext>`... once it reaches
+/// `max_file_bytes`, keeping at most `max_files` files in total
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotatingFileConfig {
+    pub path: PathBuf,
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+fn default_max_file_bytes() -> u64 {
+    1024 * 1024 // 1 MiB
+}
+
+fn default_max_files() -> usize {
+    4
+}
+
+/// Picks up config file changes while running, see `Config::reload`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReloadConfig {
+    /// Reload when the config files change on disk
+    #[serde(default = "default_watch")]
+    pub watch: bool,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        ReloadConfig {
+            watch: default_watch(),
+            poll_interval_ms: default_poll_interval_ms(),
+        }
+    }
+}
+
+fn default_watch() -> bool {
+    true
+}
+
+fn default_poll_interval_ms() -> u64 {
+    2000
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Dispatchers to run concurrently
+    #[serde(
+        default = "default_dispatch_strategies",
+        deserialize_with = "one_or_many"
+    )]
+    pub command_dispatch_strategy: Vec<CommandDispatchStrategy>,
     pub dispatcher: Dispatcher,
+    #[serde(default)]
+    pub arbitration: ArbitrationConfig,
     pub telemetry: Option<TelemetryConfig>,
+    pub status: Option<StatusConfig>,
+    pub bms: Option<BmsConfig>,
+    #[serde(default)]
+    pub estop: EstopConfig,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    /// Keyed by the lowercase resource name. Resources without a section use the defaults.
+    #[serde(default)]
+    pub managers: HashMap<String, ManagerConfig>,
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Black-box journal of significant events, see `journal`
+    pub journal: Option<RotatingFileConfig>,
 }
 
-static CONFIG: OnceLock<Config> = OnceLock::new();
+/// Config the process started with
+static CONFIG: OnceLock<Arc<Config>> = OnceLock::new();
+/// Latest config applied by `Config::reload`, if any
+static CURRENT: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+/// Arguments the config was loaded with, reused on reload
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+/// Environment variables with this prefix override config values
+const ENV_PREFIX: &str = "GPM_";
+/// Names the base config file rather than overriding a value
+const CONFIG_PATH_ENV: &str = "GPM_CONFIG";
+/// Separates nested keys in environment overrides, i.e. GPM_DISPATCHER__TCP__ADDRESS
+const ENV_PATH_SEPARATOR: &str = "__";
 
 pub const fn get_config_path() -> &'static str {
     if cfg!(feature = "pi") {
@@ -70,33 +729,219 @@ pub const fn get_config_path() -> &'static str {
 }
 
 impl Config {
+    /// Loads and validates the config and makes it available through `Config::global`. Must run
+    /// before anything reads the config.
+    pub fn init(args: &Args) -> Result<&'static Self> {
+        let config = Config::load(args)?;
+        let _ = ARGS.set(args.clone());
+        Ok(CONFIG.get_or_init(|| Arc::new(config)))
+    }
+
+    /// Returns the config GPM started with, set up by `Config::init`, falling back to loading the
+    /// default config (i.e. in tests). Use `Config::current` for settings that can be reloaded.
     pub fn global() -> &'static Self {
+        Config::startup()
+    }
+
+    fn startup() -> &'static Arc<Self> {
         CONFIG.get_or_init(|| {
-            let config_path = get_config_path();
-            let content = fs::read_to_string(config_path).expect("Failed to read config");
-            toml::from_str(&content).expect("Failed to parse config")
+            Arc::new(Config::load(&Args::default()).unwrap_or_else(|e| panic!("{:?}", e)))
+        })
+    }
+
+    /// Returns the latest config, including runtime tunable changes applied by `Config::reload`
+    pub fn current() -> Arc<Self> {
+        CURRENT
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Config::startup().clone())
+    }
+
+    /// Loads the config again and makes it current, unless something changed that needs a restart.
+    /// Returns the changed keys.
+    pub fn reload() -> Result<Vec<String>> {
+        let default_args = Args::default();
+        let new = Config::load(ARGS.get().unwrap_or(&default_args))?;
+
+        let mut current = CURRENT.write().unwrap();
+        let old = current.clone().unwrap_or_else(|| Config::startup().clone());
+        let changes = diff::changed_keys(&old, &new)?;
+        let needs_restart: Vec<&String> = changes
+            .iter()
+            .filter(|key| !diff::is_runtime_tunable(key))
+            .collect();
+        if !needs_restart.is_empty() {
+            return Err(Error::msg(format!(
+                "Config reload rejected, these changes need a restart: {}",
+                needs_restart
+                    .iter()
+                    .map(|key| key.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        *current = Some(Arc::new(new));
+        Ok(changes)
+    }
+
+    /// Files the config is read from, whether or not they exist
+    pub fn source_paths() -> Vec<PathBuf> {
+        let base_path = base_path(ARGS.get().unwrap_or(&Args::default()));
+        let local_path = base_path.with_extension("local.toml");
+        vec![base_path, local_path]
+    }
+
+    /// Settings for the given resource's manager, i.e. "EMG"
+    pub fn manager(&self, resource: &str) -> ManagerConfig {
+        self.managers
+            .get(&resource.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Deadline for tasks sent to the given resource
+    pub fn task_deadline(&self, resource: &str) -> Duration {
+        Duration::from_millis(self.manager(resource).deadline_ms)
+    }
+
+    /// Builds the config from, in increasing order of precedence, the base file, an optional
+    /// `<base>.local.toml` next to it, `GPM_*` environment variables and the command line
+    pub fn load(args: &Args) -> Result<Self> {
+        let base_path = base_path(args);
+        let mut value = read_layer(&base_path)?;
+
+        let local_path = base_path.with_extension("local.toml");
+        if local_path.exists() {
+            merge(&mut value, read_layer(&local_path)?);
+        }
+
+        let mut env_overrides: Vec<(String, String)> = env::vars()
+            .filter(|(key, _)| key.starts_with(ENV_PREFIX) && key != CONFIG_PATH_ENV)
+            .collect();
+        env_overrides.sort();
+        for (key, raw) in &env_overrides {
+            apply_env_override(&mut value, key, raw)?;
+        }
+
+        if let Some(strategy) = &args.strategy {
+            set(
+                &mut value,
+                &["command_dispatch_strategy".to_string()],
+                Value::try_from(strategy)?,
+            )?;
+        }
+
+        let config: Config = value
+            .try_into()
+            .map_err(|e| Error::msg(format!("Invalid config {:?}: {}", base_path, e)))?;
+        check_env_overrides(&config, env_overrides.iter().map(|(key, _)| key.as_str()))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Path of the base config file the given arguments select
+pub fn base_path(args: &Args) -> PathBuf {
+    args.config
+        .clone()
+        .or_else(|| env::var_os(CONFIG_PATH_ENV).map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(get_config_path()))
+}
+
+fn read_layer(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Failed to read config {:?}: {}", path, e)))?;
+    let table: Table = toml::from_str(&content)
+        .map_err(|e| Error::msg(format!("Failed to parse config {:?}: {}", path, e)))?;
+    Ok(Value::Table(table))
+}
+
+/// Recursively merges `overlay` into `base`, values in `overlay` win
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Applies `GPM_A__B=raw` as `a.b = raw`. The value is read as TOML when possible (numbers,
+/// booleans, arrays, ...) and as a plain string otherwise.
+fn apply_env_override(root: &mut Value, key: &str, raw: &str) -> Result<()> {
+    let path = env_override_path(key);
+    let value = toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()));
+    set(root, &path, value).map_err(|e| Error::msg(format!("Invalid override {}: {}", key, e)))
+}
+
+/// Config keys `GPM_A__B` sets, i.e. `["a", "b"]`
+fn env_override_path(key: &str) -> Vec<String> {
+    key[ENV_PREFIX.len()..]
+        .split(ENV_PATH_SEPARATOR)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Returns an `Err` listing the overrides that don't set any config field. They would be dropped
+/// silently while deserializing, leaving a misspelt setting unapplied.
+fn check_env_overrides<'a>(config: &Config, keys: impl Iterator<Item = &'a str>) -> Result<()> {
+    let fields = Value::try_from(config)?;
+    let unknown: Vec<&str> = keys
+        .filter(|key| {
+            env_override_path(key)
+                .iter()
+                .try_fold(&fields, |value, field| value.get(field))
+                .is_none()
         })
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(Error::msg(format!(
+        "Unknown config overrides, they match no config field: {}",
+        unknown.join(", ")
+    )))
+}
+
+fn set(root: &mut Value, path: &[String], value: Value) -> Result<()> {
+    let (last, parents) = path.split_last().ok_or(Error::msg("Empty config key"))?;
+    let mut table = root
+        .as_table_mut()
+        .ok_or(Error::msg("Config root is not a table"))?;
+    for key in parents {
+        table = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or(Error::msg(format!("{} is not a table", key)))?;
     }
+    table.insert(last.clone(), value);
+    Ok(())
 }
 
-// Logging configs and constants
-const LOG_LEVEL: LevelFilter = LevelFilter::Trace;
-const GRASP_ASCII: &str = r"
-   ______                    
-  / ____/________ __________ 
- / / __/ ___/ __ `/ ___/ __ \
-/ /_/ / /  / /_/ (__  ) /_/ /
-\____/_/   \__,_/____/ .___/ 
-                    /_/      ";
-const VERSION_LINE: &str = "Grasp primary control module | Version 0.0.1";
-const BYLINE: &str = "Developed at UBC Bionics (http://www.ubcbionics.com)";
-const NEW_LINE: &str = "\n";
-
-/// Initializes env_logger and prints metadata
-pub fn logger_init() {
-    env_logger::builder().filter_level(LOG_LEVEL).init();
-    println!("{}", GRASP_ASCII);
-    println!("{}", VERSION_LINE);
-    println!("{}", BYLINE);
-    println!("{}", NEW_LINE);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_overrides_of_unknown_fields() {
+        let config = Config::global();
+        let check = |keys: &[&str]| check_env_overrides(config, keys.iter().copied());
+        assert!(check(&["GPM_DISPATCHER__TCP__ADDRESS", "GPM_ESTOP__ACTION"]).is_ok());
+
+        let err = check(&["GPM_DISPATCHER__TCP__ADRESS", "GPM_ESTOP__ACTION"]).unwrap_err();
+        assert!(err.to_string().ends_with("GPM_DISPATCHER__TCP__ADRESS"));
+    }
 }
@@ -0,0 +1,128 @@
+// Compares two configs key by key to decide whether a reload can be applied while running
+use super::Config;
+use anyhow::Result;
+use toml::Value;
+
+/// Keys that may change without a restart, along with everything nested under them. `*` matches
+/// any array index.
+const RUNTIME_TUNABLE: &[&str] = &[
+    "arbitration.priority",
+    "arbitration.override_timeout_ms",
+    "estop.action",
+    "dispatcher.http.max_body_size_in_bytes",
+    "dispatcher.emg.sampling_speed_ms",
+    "dispatcher.emg.pause_duration_ms",
+    "dispatcher.emg.buffer_size",
+    "dispatcher.emg.envelope_smoothing",
+    "dispatcher.emg.channels.*.gain",
+    "bms.low_voltage",
+    "bms.recovery_voltage",
+    "bms.poll_interval_ms",
+];
+
+/// Returns the dotted keys whose values differ, i.e. "dispatcher.emg.channels.0.gain". Arrays
+/// that changed length are reported as a whole.
+pub fn changed_keys(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+    collect_changes(
+        "",
+        &Value::try_from(old)?,
+        &Value::try_from(new)?,
+        &mut changes,
+    );
+    Ok(changes)
+}
+
+pub fn is_runtime_tunable(key: &str) -> bool {
+    RUNTIME_TUNABLE.iter().any(|pattern| {
+        let pattern: Vec<&str> = pattern.split('.').collect();
+        let key: Vec<&str> = key.split('.').collect();
+        key.len() >= pattern.len()
+            && pattern.iter().zip(&key).all(|(expected, actual)| {
+                expected == actual
+                    || (*expected == "*" && actual.chars().all(|c| c.is_ascii_digit()))
+            })
+    })
+}
+
+fn collect_changes(path: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+    let child = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match (old, new) {
+        (Value::Table(old), Value::Table(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => collect_changes(&child(key), old, new, changes),
+                    _ => changes.push(child(key)),
+                }
+            }
+        },
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                collect_changes(&child(&i.to_string()), old, new, changes);
+            }
+        },
+        (old, new) if old != new => changes.push(path.to_string()),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [dispatcher.tcp]
+            max_concurrent_connections = 1
+            address = "127.0.0.1:4760"
+            read_buffer_capacity_in_bytes = 1024
+            frame_prefix_length_in_bytes = 8
+            {}
+            "#,
+            extra
+        ))
+        .unwrap()
+    }
+
+    const EMG: &str = r#"
+        [dispatcher.emg]
+        buffer_size = 100
+        pause_duration_ms = 500
+        sampling_speed_ms = 1000
+        cs_pin = 17
+    "#;
+
+    #[test]
+    fn reports_changed_keys() {
+        let old = config(EMG);
+        let new = config(&format!(
+            "{}\n[arbitration]\noverride_timeout_ms = 100",
+            EMG.replace("cs_pin = 17", "cs_pin = 5")
+        ));
+        assert_eq!(
+            changed_keys(&old, &new).unwrap(),
+            vec!["arbitration.override_timeout_ms", "dispatcher.emg.cs_pin"]
+        );
+        assert!(changed_keys(&old, &config(EMG)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_listed_keys_are_runtime_tunable() {
+        assert!(is_runtime_tunable("dispatcher.emg.channels.1.gain"));
+        assert!(is_runtime_tunable("arbitration.priority.0"));
+        assert!(!is_runtime_tunable("dispatcher.emg.channels.1.channel"));
+        assert!(!is_runtime_tunable("dispatcher.emg.channels"));
+        assert!(!is_runtime_tunable("dispatcher.tcp.address"));
+    }
+}
@@ -0,0 +1,157 @@
+// Logger setup driven by the `[logging]` config section
+use super::Config;
+use super::LogFormat;
+use crate::journal::rotating::RotatingFile;
+use env_logger::Target;
+use env_logger::fmt::Formatter;
+use log::LevelFilter;
+use log::Record;
+use log::kv;
+use serde_json::Map;
+use serde_json::Value;
+use std::io;
+use std::io::Write;
+
+const GRASP_ASCII: &str = r"
+   ______                    
+  / ____/________ __________ 
+ / / __/ ___/ __ `/ ___/ __ \
+/ /_/ / /  / /_/ (__  ) /_/ /
+\____/_/   \__,_/____/ .___/ 
+                    /_/      ";
+const VERSION_LINE: &str = "Grasp primary control module | Version 0.0.1";
+const BYLINE: &str = "Developed at UBC Bionics (http://www.ubcbionics.com)";
+const NEW_LINE: &str = "\n";
+
+/// Initializes env_logger and prints metadata. Filters come from the config, overridden by
+/// `RUST_LOG`. A `log_level` (from `--log-level`) replaces both and applies to every module.
+pub fn logger_init(log_level: Option<LevelFilter>) {
+    let logging_config = &Config::global().logging;
+
+    let mut builder = env_logger::builder();
+    match log_level {
+        Some(level) => {
+            builder.filter_level(level);
+        },
+        None => {
+            builder.filter_level(logging_config.level);
+            for (module, level) in &logging_config.filters {
+                builder.filter_module(module, *level);
+            }
+            builder.parse_default_env();
+        },
+    }
+    if logging_config.format == LogFormat::Json {
+        builder.format(format_json);
+    }
+    if let Some(file_config) = &logging_config.file {
+        match RotatingFile::open(file_config) {
+            Ok(file) => {
+                builder.target(Target::Pipe(Box::new(TeeStderr(file))));
+            },
+            Err(err) => eprintln!("Failed to open log file {:?}: {}", file_config.path, err),
+        }
+    }
+    builder.init();
+
+    if logging_config.banner {
+        println!("{}", GRASP_ASCII);
+        println!("{}", VERSION_LINE);
+        println!("{}", BYLINE);
+        println!("{}", NEW_LINE);
+    }
+}
+
+/// Copies everything written to the log file to stderr as well
+struct TeeStderr(RotatingFile);
+
+impl Write for TeeStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A full disk shouldn't hide the logs on stderr too
+        let _ = self.0.write_all(buf);
+        io::stderr().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _ = self.0.flush();
+        io::stderr().flush()
+    }
+}
+
+/// Writes the record as a single line JSON object
+fn format_json(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    let mut line = json_fields(record);
+    line.insert(
+        "timestamp".to_string(),
+        Value::String(chrono::Utc::now().to_rfc3339()),
+    );
+    writeln!(buf, "{}", Value::Object(line))
+}
+
+fn json_fields(record: &Record) -> Map<String, Value> {
+    let mut fields = Map::new();
+    fields.insert(
+        "level".to_string(),
+        Value::String(record.level().to_string()),
+    );
+    fields.insert(
+        "target".to_string(),
+        Value::String(record.target().to_string()),
+    );
+    fields.insert(
+        "message".to_string(),
+        Value::String(record.args().to_string()),
+    );
+    // Key-values can't fail to be visited into a map
+    let _ = record.key_values().visit(&mut JsonVisitor(&mut fields));
+    fields
+}
+
+/// Collects a record's key-values, keeping numbers and booleans as JSON numbers and booleans
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> kv::VisitSource<'kvs> for JsonVisitor<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            Value::Bool(value)
+        } else if let Some(value) = value.to_i64() {
+            Value::from(value)
+        } else if let Some(value) = value.to_u64() {
+            Value::from(value)
+        } else if let Some(value) = value.to_f64() {
+            Value::from(value)
+        } else {
+            Value::String(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattens_key_values() {
+        let kvs: [(&str, kv::Value); 3] = [
+            ("resource", "MAESTRO".into()),
+            ("task", "OPEN_FIST".into()),
+            ("queue_depth", 3u64.into()),
+        ];
+        let record = Record::builder()
+            .args(format_args!("Dispatching task"))
+            .level(log::Level::Info)
+            .target("gpm::dispatchers")
+            .key_values(&kvs)
+            .build();
+
+        let fields = json_fields(&record);
+        assert_eq!(fields["message"], "Dispatching task");
+        assert_eq!(fields["level"], "INFO");
+        assert_eq!(fields["resource"], "MAESTRO");
+        assert_eq!(fields["task"], "OPEN_FIST");
+        assert_eq!(fields["queue_depth"], 3);
+    }
+}
@@ -0,0 +1,371 @@
+// Checks that go beyond what deserialization can express, run before any manager starts so that a
+// bad config is reported up front instead of as a panic deep inside a dispatcher
+use super::AdcKind;
+use super::BmsConfig;
+use super::CommandDispatchStrategy;
+use super::Config;
+use super::EmgConfig;
+use crate::managers::registry::ResourceRegistry;
+use crate::sgcp;
+use anyhow::Error;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Frames are prefixed with their length as a u64
+const FRAME_PREFIX_LENGTH_IN_BYTES: i32 = 8;
+
+impl Config {
+    /// Returns an `Err` listing every problem found
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if self.command_dispatch_strategy.is_empty() {
+            errors.push("command_dispatch_strategy must list at least one strategy".to_string());
+        }
+        if let Some(strategy) = duplicate(&self.command_dispatch_strategy) {
+            errors.push(format!(
+                "command_dispatch_strategy lists {:?} more than once",
+                strategy
+            ));
+        }
+        if let Some(strategy) = duplicate(&self.arbitration.priority) {
+            errors.push(format!(
+                "arbitration.priority lists {:?} more than once",
+                strategy
+            ));
+        }
+
+        let tcp = &self.dispatcher.tcp;
+        if tcp.max_concurrent_connections <= 0 {
+            errors.push(format!(
+                "dispatcher.tcp.max_concurrent_connections must be positive, got {}",
+                tcp.max_concurrent_connections
+            ));
+        }
+        if tcp.read_buffer_capacity_in_bytes <= 0 {
+            errors.push(format!(
+                "dispatcher.tcp.read_buffer_capacity_in_bytes must be positive, got {}",
+                tcp.read_buffer_capacity_in_bytes
+            ));
+        }
+        if tcp.frame_prefix_length_in_bytes != FRAME_PREFIX_LENGTH_IN_BYTES {
+            errors.push(format!(
+                "dispatcher.tcp.frame_prefix_length_in_bytes must be {}, got {}",
+                FRAME_PREFIX_LENGTH_IN_BYTES, tcp.frame_prefix_length_in_bytes
+            ));
+        }
+
+        for strategy in &self.command_dispatch_strategy {
+            let (configured, section) = match strategy {
+                CommandDispatchStrategy::Tcp => (true, ""),
+                CommandDispatchStrategy::Http => {
+                    (self.dispatcher.http.is_some(), "[dispatcher.http]")
+                },
+                CommandDispatchStrategy::Gpio => (
+                    self.dispatcher.gpio_monitor.is_some(),
+                    "[dispatcher.gpio_monitor]",
+                ),
+                CommandDispatchStrategy::Emg => (self.dispatcher.emg.is_some(), "[dispatcher.emg]"),
+            };
+            if !configured {
+                errors.push(format!(
+                    "{} is required when the {:?} strategy is enabled",
+                    section, strategy
+                ));
+            }
+        }
+
+        match &self.dispatcher.emg {
+            Some(emg) => validate_emg(emg, &mut errors),
+            // The EMG resource manager always starts on the Pi
+            None if cfg!(feature = "pi") => {
+                errors.push("[dispatcher.emg] is required on the Pi".to_string());
+            },
+            None => (),
+        }
+
+        if let Some(bms) = &self.bms {
+            validate_bms(bms, &mut errors);
+        }
+
+        let registry = ResourceRegistry::global();
+        for (resource, manager) in &self.managers {
+            if registry.lookup(&resource.to_uppercase()).is_none() {
+                errors.push(format!("[managers.{}] names an unknown resource", resource));
+            }
+            if manager.buffer_size == 0 {
+                errors.push(format!(
+                    "managers.{}.buffer_size must be positive",
+                    resource
+                ));
+            }
+            if manager.deadline_ms == 0 {
+                errors.push(format!(
+                    "managers.{}.deadline_ms must be positive",
+                    resource
+                ));
+            }
+        }
+
+        if let Some(gpio_monitor) = &self.dispatcher.gpio_monitor {
+            for binding in gpio_monitor.buttons.iter().flat_map(|b| &b.bindings) {
+                if registry.lookup(&binding.resource).is_none() {
+                    errors.push(format!(
+                        "dispatcher.gpio_monitor binds {:?} to unknown resource {:?}",
+                        binding.task_code, binding.resource
+                    ));
+                }
+            }
+        }
+
+        if let Some(rest_task) = &self.shutdown.rest_task {
+            let request = sgcp::Request {
+                resource: sgcp::Resource::Maestro as i32,
+                task_code: rest_task.clone(),
+                task_data: None,
+            };
+            let is_maestro_task = registry
+                .get(sgcp::Resource::Maestro)
+                .is_some_and(|maestro| (maestro.decode)(&request).is_ok());
+            if !is_maestro_task {
+                errors.push(format!(
+                    "shutdown.rest_task {:?} is not a Maestro task",
+                    rest_task
+                ));
+            }
+        }
+
+        if let Some(telemetry) = &self.telemetry {
+            if telemetry.tick_interval_in_seconds <= 0 {
+                errors.push(format!(
+                    "telemetry.tick_interval_in_seconds must be positive, got {}",
+                    telemetry.tick_interval_in_seconds
+                ));
+            }
+            if telemetry.stream.rate_hz == 0 {
+                errors.push("telemetry.stream.rate_hz must be positive".to_string());
+            }
+            if let Some(push) = &telemetry.push {
+                if !push.url.starts_with("http://") {
+                    errors.push(format!(
+                        "telemetry.push.url must be a plain http url, got {:?}",
+                        push.url
+                    ));
+                }
+                if push.interval_in_seconds == 0 {
+                    errors.push("telemetry.push.interval_in_seconds must be positive".to_string());
+                }
+            }
+        }
+
+        let rotating_files = [
+            ("logging.file", self.logging.file.as_ref()),
+            ("journal", self.journal.as_ref()),
+        ];
+        for (section, file) in rotating_files {
+            if let Some(file) = file
+                && (file.max_file_bytes == 0 || file.max_files == 0)
+            {
+                errors.push(format!(
+                    "{}.max_file_bytes and {}.max_files must be positive",
+                    section, section
+                ));
+            }
+        }
+
+        if self.supervisor.initial_backoff_ms == 0
+            || self.supervisor.initial_backoff_ms > self.supervisor.max_backoff_ms
+        {
+            errors.push(format!(
+                "supervisor.initial_backoff_ms must be positive and at most max_backoff_ms ({}), got {}",
+                self.supervisor.max_backoff_ms, self.supervisor.initial_backoff_ms
+            ));
+        }
+
+        validate_pins(self, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::msg(format!(
+                "Invalid config:\n{}",
+                errors
+                    .iter()
+                    .map(|error| format!("  - {}", error))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )))
+        }
+    }
+}
+
+fn validate_emg(emg: &EmgConfig, errors: &mut Vec<String>) {
+    if emg.buffer_size == 0 {
+        errors.push("dispatcher.emg.buffer_size must be positive".to_string());
+    }
+    if !(emg.envelope_smoothing > 0.0 && emg.envelope_smoothing <= 1.0) {
+        errors.push(format!(
+            "dispatcher.emg.envelope_smoothing must be in (0, 1], got {}",
+            emg.envelope_smoothing
+        ));
+    }
+    if emg.enabled_channels().next().is_none() {
+        errors.push("dispatcher.emg must enable at least one channel".to_string());
+    }
+
+    let channel_count = channel_count(&emg.adc.kind);
+    for electrode in emg.enabled_channels() {
+        for channel in [Some(electrode.channel), electrode.reference_channel]
+            .into_iter()
+            .flatten()
+        {
+            if channel >= channel_count {
+                errors.push(format!(
+                    "dispatcher.emg electrode {:?} uses channel {}, the {:?} only has {}",
+                    electrode.label, channel, emg.adc.kind, channel_count
+                ));
+            }
+        }
+    }
+}
+
+fn validate_bms(bms: &BmsConfig, errors: &mut Vec<String>) {
+    let channel_count = channel_count(&bms.adc.kind);
+    if bms.channel >= channel_count {
+        errors.push(format!(
+            "bms.channel is {}, the {:?} only has {}",
+            bms.channel, bms.adc.kind, channel_count
+        ));
+    }
+    if bms.divider_ratio <= 0.0 {
+        errors.push(format!(
+            "bms.divider_ratio must be positive, got {}",
+            bms.divider_ratio
+        ));
+    }
+    if bms.recovery_voltage() < bms.low_voltage {
+        errors.push(format!(
+            "bms.recovery_voltage must be at least low_voltage ({}), got {}",
+            bms.low_voltage,
+            bms.recovery_voltage()
+        ));
+    }
+    if bms.poll_interval_ms == 0 {
+        errors.push("bms.poll_interval_ms must be positive".to_string());
+    }
+}
+
+fn channel_count(kind: &AdcKind) -> u8 {
+    match kind {
+        AdcKind::Mcp3008 | AdcKind::Mcp3208 => 8,
+        AdcKind::Ads1115 => 4,
+    }
+}
+
+/// Every GPIO pin may only have one owner
+fn validate_pins(config: &Config, errors: &mut Vec<String>) {
+    let mut pins: Vec<(u8, String)> = Vec::new();
+    if let Some(pin) = config.estop.pin {
+        pins.push((pin, "the e-stop".to_string()));
+    }
+    if let Some(gpio_monitor) = &config.dispatcher.gpio_monitor {
+        for button in &gpio_monitor.buttons {
+            pins.push((button.pin, "a button".to_string()));
+        }
+    }
+    if let Some(emg) = &config.dispatcher.emg {
+        pins.push((emg.cs_pin, "the EMG chip select".to_string()));
+    }
+    if let Some(bms) = &config.bms {
+        pins.push((bms.cs_pin, "the BMS chip select".to_string()));
+    }
+    if let Some(status) = &config.status {
+        for indicator in &status.indicators {
+            pins.push((indicator.pin, format!("the {:?} indicator", indicator.name)));
+        }
+    }
+
+    let mut owners: HashMap<u8, &str> = HashMap::new();
+    for (pin, owner) in &pins {
+        if let Some(first_owner) = owners.insert(*pin, owner) {
+            errors.push(format!(
+                "GPIO pin {} is used by both {} and {}",
+                pin, first_owner, owner
+            ));
+        }
+    }
+}
+
+fn duplicate<T: Copy + Eq + std::hash::Hash>(items: &[T]) -> Option<T> {
+    let mut seen = HashSet::new();
+    items.iter().copied().find(|item| !seen.insert(*item))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(content: &str) -> Config {
+        toml::from_str(content).unwrap()
+    }
+
+    const TCP: &str = r#"
+        [dispatcher.tcp]
+        max_concurrent_connections = 1
+        address = "127.0.0.1:4760"
+        read_buffer_capacity_in_bytes = 1024
+        frame_prefix_length_in_bytes = 8
+    "#;
+
+    #[test]
+    fn default_configs_are_valid() {
+        Config::global().validate().unwrap();
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let config = config(&format!(
+            r#"
+            command_dispatch_strategy = ["emg", "http"]
+            [estop]
+            pin = 2
+            [shutdown]
+            rest_task = "WAVE"
+            [dispatcher.gpio_monitor]
+            [[dispatcher.gpio_monitor.buttons]]
+            pin = 2
+            bindings = [{{ press = "short", resource = "SERVOS", task_code = "OPEN_FIST" }}]
+            [bms]
+            cs_pin = 2
+            channel = 8
+            low_voltage = 10.5
+            recovery_voltage = 10.0
+            {}
+            "#,
+            TCP.replace(
+                "max_concurrent_connections = 1",
+                "max_concurrent_connections = -1"
+            )
+        ));
+        let message = config.validate().unwrap_err().to_string();
+        for expected in [
+            "max_concurrent_connections must be positive, got -1",
+            "[dispatcher.emg] is required when the Emg strategy is enabled",
+            "[dispatcher.http] is required when the Http strategy is enabled",
+            "unknown resource \"SERVOS\"",
+            "GPIO pin 2 is used by both the e-stop and a button",
+            "shutdown.rest_task \"WAVE\" is not a Maestro task",
+            "bms.channel is 8, the Mcp3008 only has 8",
+            "bms.recovery_voltage must be at least low_voltage (10.5), got 10",
+            "GPIO pin 2 is used by both a button and the BMS chip select",
+        ] {
+            assert!(
+                message.contains(expected),
+                "{:?} not in {}",
+                expected,
+                message
+            );
+        }
+    }
+}
@@ -1,29 +1,267 @@
+pub mod arbiter;
 pub mod emg;
+mod error;
+pub mod estop;
 pub mod gpio;
-mod macros;
+pub mod http;
+pub mod reload;
+pub mod shutdown;
 pub mod tcp;
 
 use crate::ManagerChannelMap;
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
+use crate::journal;
+use crate::journal::Event;
 use crate::managers::ManagerChannelData;
+use crate::managers::RELOAD_CONFIG_TASK;
+use crate::managers::TASK_ERROR_PREFIX;
+use crate::managers::queue::Priority;
+use crate::managers::registry::ResourceRegistry;
 use crate::sgcp;
-use anyhow::Error;
+use crate::telemetry::tasks::TASK_METRICS;
 use anyhow::Result;
 use log::info;
+use log::warn;
+use std::time::Duration;
+use std::time::Instant;
 use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+pub use error::DispatchError;
 
 pub trait Dispatcher {
     async fn run(manager_channel_map: ManagerChannelMap);
 }
 
-/// Dispatches a request to the appropiate resource manager. Returns the response from the task.
+/// Dispatches a request to the appropiate resource manager on behalf of the `source` dispatcher.
+/// Returns the response from the task.
 pub async fn dispatch_task(
     request: sgcp::Request,
+    source: CommandDispatchStrategy,
     manager_channel_map: &ManagerChannelMap,
 ) -> Result<String> {
-    macros::dispatch_task!(
-        request,
-        sgcp::Resource::Bms => manager_channel_map.get(sgcp::Resource::Bms.as_str_name()),
-        sgcp::Resource::Emg => manager_channel_map.get(sgcp::Resource::Emg.as_str_name()),
-        sgcp::Resource::Maestro => manager_channel_map.get(sgcp::Resource::Maestro.as_str_name())
-    )
+    dispatch_task_with_deadline(request, source, manager_channel_map, None).await
+}
+
+/// Like `dispatch_task`, but gives up waiting for the resource manager after `deadline` instead
+/// of the resource's configured deadline
+pub async fn dispatch_task_with_deadline(
+    request: sgcp::Request,
+    source: CommandDispatchStrategy,
+    manager_channel_map: &ManagerChannelMap,
+    deadline: Option<Duration>,
+) -> Result<String> {
+    let resource_key = request.resource().as_str_name();
+    let task_code = request.task_code.clone();
+    let task_label = task_label(&request);
+    let routine = ResourceRegistry::global().is_routine(&request);
+    TASK_METRICS.record_dispatched(resource_key, &task_label);
+
+    let started_at = Instant::now();
+    let in_flight = shutdown::InFlight::start();
+    let result = route_task(request, source, manager_channel_map, deadline).await;
+    drop(in_flight);
+    TASK_METRICS.record_outcome(resource_key, &task_label, started_at.elapsed(), &result);
+    let failure = failure_kind(&result);
+    if failure.is_some() || !routine {
+        journal::record(Event::TaskDispatched {
+            source,
+            resource: resource_key.to_string(),
+            outcome: failure.unwrap_or("ok").to_string(),
+            task_code,
+        });
+    }
+    result
+}
+
+/// Task code to label a request's metrics with. Codes its resource doesn't know are labelled
+/// "unknown", so clients can't add metric series without bound.
+fn task_label(request: &sgcp::Request) -> String {
+    let known = match request.task_code.as_str() {
+        estop::ESTOP_TASK | estop::RESET_ESTOP_TASK | RELOAD_CONFIG_TASK => true,
+        _ => ResourceRegistry::global()
+            .get(request.resource())
+            .is_some_and(|registration| {
+                !matches!(
+                    (registration.decode)(request),
+                    Err(DispatchError::UnknownTask { .. })
+                )
+            }),
+    };
+    if known {
+        request.task_code.clone()
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// Classifies a failed task result, `None` if the task succeeded
+pub fn failure_kind(result: &Result<String>) -> Option<&'static str> {
+    match result {
+        Ok(response) if response.starts_with(TASK_ERROR_PREFIX) => Some("task_error"),
+        Ok(_) => None,
+        Err(err) => Some(
+            err.downcast_ref::<DispatchError>()
+                .map_or("other", DispatchError::kind),
+        ),
+    }
+}
+
+async fn route_task(
+    request: sgcp::Request,
+    source: CommandDispatchStrategy,
+    manager_channel_map: &ManagerChannelMap,
+    deadline: Option<Duration>,
+) -> Result<String> {
+    // The e-stop must keep working while shutting down
+    if request.task_code != estop::ESTOP_TASK {
+        shutdown::check()?;
+    }
+
+    match request.task_code.as_str() {
+        estop::ESTOP_TASK => {
+            return estop::engage(&format!("{:?} dispatcher", source), manager_channel_map).await;
+        },
+        estop::RESET_ESTOP_TASK => return estop::reset(),
+        RELOAD_CONFIG_TASK => {
+            return reload::reload(&format!("{:?} dispatcher", source), manager_channel_map).await;
+        },
+        _ => (),
+    }
+
+    let registration = ResourceRegistry::global()
+        .get(request.resource())
+        .ok_or(DispatchError::UnknownResource)?;
+    (registration.decode)(&request)?;
+
+    // Turned away early here, the Maestro manager checks again right before it moves
+    if request.resource() == sgcp::Resource::Maestro {
+        estop::check()?;
+        arbiter::check_motion(source)?;
+    }
+
+    let resource_key = registration.key();
+    let deadline = deadline.unwrap_or_else(|| Config::current().task_deadline(resource_key));
+    let dispatch = send_task(request, source, resource_key, manager_channel_map);
+    // Dropping `dispatch` on expiry drops the response channel, so a late response is discarded
+    let response = timeout(deadline, dispatch).await.map_err(|_| {
+        warn!(
+            resource = resource_key;
+            "{} task timed out after {:?}", resource_key, deadline
+        );
+        DispatchError::Timeout {
+            resource: resource_key,
+            deadline,
+        }
+    })??;
+    Ok(response)
+}
+
+/// Queues the task for its resource manager and waits for the response
+async fn send_task(
+    request: sgcp::Request,
+    source: CommandDispatchStrategy,
+    resource_key: &'static str,
+    manager_channel_map: &ManagerChannelMap,
+) -> Result<String> {
+    info!(
+        resource = resource_key, task = request.task_code.as_str();
+        "Dispatching {:?} task with task_code={:?}", resource_key, request.task_code
+    );
+    let queue = manager_channel_map
+        .get(resource_key)
+        .ok_or(DispatchError::NotInitialized(resource_key))?;
+
+    // Set up channel on which manager will send its response
+    let (resp_tx, resp_rx) = oneshot::channel::<String>();
+    queue
+        .send(ManagerChannelData {
+            task_code: request.task_code,
+            task_data: request.task_data,
+            priority: Priority::Normal,
+            source: Some(source),
+            resp_tx,
+        })
+        .await?;
+    TASK_METRICS.set_queue_depth(resource_key, queue.len());
+
+    let res = resp_rx
+        .await
+        .map_err(|_| DispatchError::NoResponse(resource_key))?;
+    info!(resource = resource_key; "{} task returned value={:?}", resource_key, res);
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackpressurePolicy;
+    use crate::managers::queue::TaskQueue;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn gives_up_on_a_hung_manager() {
+        // Nothing serves the queue, so the task is never responded to
+        let queue = TaskQueue::new("BMS", 1, BackpressurePolicy::Block);
+        let manager_channel_map = HashMap::from([(
+            sgcp::Resource::Bms.as_str_name().to_string(),
+            Arc::new(queue),
+        )]);
+        let request = sgcp::Request {
+            resource: sgcp::Resource::Bms as i32,
+            task_code: "GET_HEALTH_METRICS".to_string(),
+            task_data: None,
+        };
+
+        let err = dispatch_task_with_deadline(
+            request,
+            CommandDispatchStrategy::Tcp,
+            &manager_channel_map,
+            Some(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DispatchError>(),
+            Some(DispatchError::Timeout { .. })
+        ));
+    }
+
+    #[test]
+    fn labels_unknown_task_codes_as_unknown() {
+        let request = |resource: sgcp::Resource, task_code: &str| sgcp::Request {
+            resource: resource as i32,
+            task_code: task_code.to_string(),
+            task_data: None,
+        };
+        let label = |resource, task_code| task_label(&request(resource, task_code));
+        assert_eq!(label(sgcp::Resource::Maestro, "OPEN_FIST"), "OPEN_FIST");
+        assert_eq!(label(sgcp::Resource::Maestro, "ESTOP"), "ESTOP");
+        assert_eq!(label(sgcp::Resource::Maestro, "OPEN_FIST_123"), "unknown");
+        assert_eq!(label(sgcp::Resource::Bms, "OPEN_FIST"), "unknown");
+    }
+
+    #[tokio::test]
+    async fn failed_motion_commands_do_not_claim_the_override() {
+        // No Maestro manager, so the command fails before reaching the Maestro
+        let request = sgcp::Request {
+            resource: sgcp::Resource::Maestro as i32,
+            task_code: "OPEN_FIST".to_string(),
+            task_data: None,
+        };
+        let err = dispatch_task(request, CommandDispatchStrategy::Tcp, &HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DispatchError>(),
+            Some(DispatchError::NotInitialized(_))
+        ));
+        // TCP ranks highest, so it would hold the override had the command been recorded
+        assert_ne!(
+            arbiter::override_holder(),
+            Some(CommandDispatchStrategy::Tcp)
+        );
+    }
 }
@@ -0,0 +1,64 @@
+// Arbitration between dispatchers running side by side. A Maestro command from one dispatcher
+// locks out every lower priority dispatcher for `override_timeout_ms`, so that e.g. a remote
+// override over TCP is not immediately undone by the EMG loop.
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
+use crate::dispatchers::error::DispatchError;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Time of the last accepted Maestro command from each dispatcher
+static LAST_MOTION_COMMAND: LazyLock<Mutex<HashMap<CommandDispatchStrategy, Instant>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Lower is more important
+fn rank(source: CommandDispatchStrategy) -> usize {
+    let priority = &Config::current().arbitration.priority;
+    priority
+        .iter()
+        .position(|&strategy| strategy == source)
+        .unwrap_or(priority.len())
+}
+
+/// Returns an `Err` if a higher priority dispatcher currently holds the override
+pub fn check_motion(source: CommandDispatchStrategy) -> Result<()> {
+    let timeout = Duration::from_millis(Config::current().arbitration.override_timeout_ms);
+    let last_motion_command = LAST_MOTION_COMMAND.lock().unwrap();
+
+    if let Some((holder, _)) = last_motion_command
+        .iter()
+        .find(|(holder, issued_at)| rank(**holder) < rank(source) && issued_at.elapsed() < timeout)
+    {
+        return Err(DispatchError::Overridden {
+            holder: *holder,
+            source,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Records a Maestro command from `source` once the Maestro has carried it out
+pub fn record_motion(source: CommandDispatchStrategy) {
+    LAST_MOTION_COMMAND
+        .lock()
+        .unwrap()
+        .insert(source, Instant::now());
+}
+
+/// Returns the dispatcher whose Maestro commands currently lock out lower priority ones, if any
+pub fn override_holder() -> Option<CommandDispatchStrategy> {
+    let timeout = Duration::from_millis(Config::current().arbitration.override_timeout_ms);
+    LAST_MOTION_COMMAND
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, issued_at)| issued_at.elapsed() < timeout)
+        .map(|(&holder, _)| holder)
+        .min_by_key(|&holder| rank(holder))
+}
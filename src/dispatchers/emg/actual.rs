@@ -1,6 +1,7 @@
 use crate::ManagerChannelMap;
 
 use super::EmgDispatcher;
+use crate::config::CommandDispatchStrategy;
 use crate::config::Config;
 use crate::dispatchers::{Dispatcher, dispatch_task};
 use crate::sgcp;
@@ -15,13 +16,8 @@ impl Dispatcher for EmgDispatcher {
         // init
         init_tasks(manager_channel_map.clone()).await;
 
-        let emg_config = Config::global()
-            .dispatcher
-            .emg
-            .as_ref()
-            .expect("Expected EMG config to be defined");
-
-        let mut emg_idle = interval(Duration::from_millis(emg_config.sampling_speed_ms)); // 1000 ms for 1 Hz sampling rate for idle tasks, 2 ms for 500 Hz sampling rate
+        let mut sampling_speed = sampling_speed_ms();
+        let mut emg_idle = interval(Duration::from_millis(sampling_speed)); // 1000 ms for 1 Hz sampling rate for idle tasks, 2 ms for 500 Hz sampling rate
         let emg_response_mapping = vec![
             (
                 "OPEN HAND".to_string(),
@@ -40,6 +36,13 @@ impl Dispatcher for EmgDispatcher {
             tokio::select! {
                 _ = emg_idle.tick() => {
                     process_idle_task(&send_channel_map, sgcp::Resource::Emg, "IDLE", &emg_response_mapping).await;
+
+                    // The sampling speed can be changed by a config reload
+                    if sampling_speed != sampling_speed_ms() {
+                        sampling_speed = sampling_speed_ms();
+                        info!("EMG sampling speed changed to {} ms", sampling_speed);
+                        emg_idle = interval(Duration::from_millis(sampling_speed));
+                    }
                 }
                 // _ = HAPTICS_idle.tick() => {
                 //     // handle haptics idle task here
@@ -49,6 +52,15 @@ impl Dispatcher for EmgDispatcher {
     }
 }
 
+fn sampling_speed_ms() -> u64 {
+    Config::current()
+        .dispatcher
+        .emg
+        .as_ref()
+        .expect("Expected EMG config to be defined")
+        .sampling_speed_ms
+}
+
 /// Handles idle responses for a given resource and task code mapping
 async fn handle_idle_response(
     response: &str,
@@ -66,7 +78,7 @@ async fn handle_idle_response(
             task_data: None,
         };
 
-        match dispatch_task(request, manager_channel_map).await {
+        match dispatch_task(request, CommandDispatchStrategy::Emg, manager_channel_map).await {
             Ok(res) => info!("Task succeeded: {:?}", res),
             Err(e) => error!("Task failed: {:?}", e),
         }
@@ -88,7 +100,7 @@ async fn process_idle_task(
         task_data: None,
     };
 
-    match dispatch_task(request, manager_channel_map).await {
+    match dispatch_task(request, CommandDispatchStrategy::Emg, manager_channel_map).await {
         Ok(res) => handle_idle_response(res.as_str(), manager_channel_map, response_mapping).await,
         Err(err) => {
             error!("An error occurred when dispatching task; error={err}");
@@ -109,7 +121,7 @@ async fn init_tasks(manager_channel_map: ManagerChannelMap) {
     // can also add maestro init, move all motors to home position, 0
     let init_map = manager_channel_map.clone();
 
-    match dispatch_task(init_request, &init_map).await {
+    match dispatch_task(init_request, CommandDispatchStrategy::Emg, &init_map).await {
         Ok(_) => info!("Initialization sucess"),
         Err(err) => error!("Initialization failed: {:?}", err),
     }
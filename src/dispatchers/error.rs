@@ -0,0 +1,97 @@
+use crate::config::CommandDispatchStrategy;
+use std::fmt;
+use std::time::Duration;
+
+/// Reasons a task can be refused or lost on its way to a resource manager
+#[derive(Debug)]
+pub enum DispatchError {
+    /// The e-stop is engaged and the task would move the Maestro
+    EstopEngaged,
+    /// A higher priority dispatcher currently holds the Maestro override
+    Overridden {
+        holder: CommandDispatchStrategy,
+        source: CommandDispatchStrategy,
+    },
+    /// The request names a resource GPM does not route
+    UnknownResource,
+    /// The task code isn't one of the resource's tasks
+    UnknownTask {
+        resource: &'static str,
+        task_code: String,
+    },
+    /// The task data is meant for another resource
+    MismatchedTaskData(&'static str),
+    /// No resource manager was started for the resource
+    NotInitialized(&'static str),
+    /// The resource manager's queue is full and its backpressure policy is to reject
+    QueueFull(&'static str),
+    /// The resource manager dropped the task without responding
+    NoResponse(&'static str),
+    /// GPM is shutting down and no longer accepts tasks
+    ShuttingDown,
+    /// The resource manager didn't respond before the task's deadline. The manager may still
+    /// run the task, its response is discarded.
+    Timeout {
+        resource: &'static str,
+        deadline: Duration,
+    },
+}
+
+impl DispatchError {
+    /// Short, stable name used as the `kind` label of the failure metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DispatchError::EstopEngaged => "estop_engaged",
+            DispatchError::Overridden { .. } => "overridden",
+            DispatchError::UnknownResource => "unknown_resource",
+            DispatchError::UnknownTask { .. } => "unknown_task",
+            DispatchError::MismatchedTaskData(_) => "mismatched_task_data",
+            DispatchError::NotInitialized(_) => "not_initialized",
+            DispatchError::QueueFull(_) => "queue_full",
+            DispatchError::NoResponse(_) => "no_response",
+            DispatchError::ShuttingDown => "shutting_down",
+            DispatchError::Timeout { .. } => "timeout",
+        }
+    }
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DispatchError::EstopEngaged => write!(
+                f,
+                "Emergency stop is engaged; motion tasks are rejected until RESET_ESTOP"
+            ),
+            DispatchError::Overridden { holder, source } => write!(
+                f,
+                "Maestro is overridden by the {:?} dispatcher; rejecting command from {:?}",
+                holder, source
+            ),
+            DispatchError::UnknownResource => write!(f, "Unmatched task"),
+            DispatchError::UnknownTask {
+                resource,
+                task_code,
+            } => write!(f, "{:?} is not a {} task", task_code, resource),
+            DispatchError::MismatchedTaskData(resource) => {
+                write!(f, "Task data does not belong to {}", resource)
+            },
+            DispatchError::NotInitialized(resource) => {
+                write!(f, "{} resource manager not initialized", resource)
+            },
+            DispatchError::QueueFull(resource) => {
+                write!(f, "{} manager's queue is full; rejecting task", resource)
+            },
+            DispatchError::NoResponse(resource) => {
+                write!(f, "Failed to read response from {} manager", resource)
+            },
+            DispatchError::ShuttingDown => write!(f, "GPM is shutting down; rejecting task"),
+            DispatchError::Timeout { resource, deadline } => write!(
+                f,
+                "{} manager did not respond within {:?}",
+                resource, deadline
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DispatchError {}
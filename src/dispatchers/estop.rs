@@ -0,0 +1,169 @@
+// Emergency stop. Once engaged (by the e-stop pin or an SGCP `ESTOP` request) the Maestro is
+// halted and every Maestro task is rejected, whichever dispatcher it comes from, until an explicit
+// `RESET_ESTOP` request.
+use crate::ManagerChannelMap;
+use crate::config::Config;
+use crate::config::EstopAction;
+use crate::dispatchers::error::DispatchError;
+use crate::journal;
+use crate::journal::Event;
+use crate::managers::ManagerChannelData;
+use crate::managers::TASK_ERROR_PREFIX;
+use crate::managers::maestro::FREEZE_TASK;
+use crate::managers::maestro::RELEASE_TASK;
+use crate::managers::queue::Priority;
+use crate::resources::common::gpio::GpioInterface;
+use crate::sgcp;
+use crate::status;
+use crate::status::FaultCause;
+use anyhow::Error;
+use anyhow::Result;
+use log::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::oneshot;
+
+/// Task codes intercepted by `dispatch_task`, accepted for any resource
+pub const ESTOP_TASK: &str = "ESTOP";
+pub const RESET_ESTOP_TASK: &str = "RESET_ESTOP";
+
+static ENGAGED: AtomicBool = AtomicBool::new(false);
+
+/// Returns an `Err` while the e-stop is engaged
+pub fn check() -> Result<()> {
+    if engaged() {
+        Err(DispatchError::EstopEngaged.into())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn engaged() -> bool {
+    ENGAGED.load(Ordering::SeqCst)
+}
+
+/// Engages the e-stop and halts the Maestro
+pub async fn engage(trigger: &str, manager_channel_map: &ManagerChannelMap) -> Result<String> {
+    if !ENGAGED.swap(true, Ordering::SeqCst) {
+        error!("Emergency stop engaged by {}", trigger);
+        journal::record(Event::EstopEngaged {
+            trigger: trigger.to_string(),
+        });
+        status::raise_fault(FaultCause::Estop);
+    }
+    halt(manager_channel_map).await
+}
+
+/// Response to Maestro tasks turned away while the e-stop is engaged
+pub fn rejection() -> String {
+    format!("{}: {}", TASK_ERROR_PREFIX, DispatchError::EstopEngaged)
+}
+
+/// Rejects the motion tasks still queued for the Maestro and sends it the configured halt task
+async fn halt(manager_channel_map: &ManagerChannelMap) -> Result<String> {
+    let task_code = match Config::current().estop.action {
+        EstopAction::Freeze => FREEZE_TASK,
+        EstopAction::Release => RELEASE_TASK,
+    };
+
+    // Talks to the Maestro manager directly, the regular dispatch path is closed now
+    let maestro_tx = manager_channel_map
+        .get(sgcp::Resource::Maestro.as_str_name())
+        .ok_or(Error::msg("Maestro resource manager not initialized"))?;
+    for task in maestro_tx.drain_normal() {
+        warn!("Rejecting queued {:?} Maestro task", task.task_code);
+        let _ = task.resp_tx.send(rejection());
+    }
+    let (resp_tx, resp_rx) = oneshot::channel::<String>();
+    maestro_tx
+        .send(ManagerChannelData {
+            task_code: task_code.to_string(),
+            task_data: None,
+            priority: Priority::High,
+            source: None,
+            resp_tx,
+        })
+        .await
+        .map_err(|e| Error::msg(format!("Failed to halt Maestro: {:?}", e)))?;
+    let res = resp_rx
+        .await
+        .map_err(|e| Error::msg(format!("Failed to read response from Maestro: {:?}", e)))?;
+
+    info!("Maestro halted with {}; response={:?}", task_code, res);
+    Ok(res)
+}
+
+/// Releases the e-stop so motion tasks are accepted again. The arm only leaves `Fault` if the
+/// e-stop was all that put it there.
+pub fn reset() -> Result<String> {
+    if ENGAGED.swap(false, Ordering::SeqCst) {
+        warn!("Emergency stop reset");
+        journal::record(Event::EstopReset);
+        status::clear_fault(FaultCause::Estop);
+    }
+    Ok("Emergency stop reset".to_string())
+}
+
+/// Engages the e-stop whenever the button on `pin` is pressed (pulled low)
+pub async fn monitor_pin(
+    pin: u8,
+    mut gpio: impl GpioInterface,
+    manager_channel_map: ManagerChannelMap,
+) {
+    let (edge_tx, mut edge_rx) = unbounded_channel();
+    if let Err(err) = gpio.watch(pin, edge_tx) {
+        error!("Failed to watch e-stop pin {:?}; error={:?}", pin, err);
+        return;
+    }
+    info!("Monitoring e-stop on pin {:?}", pin);
+
+    while let Some(edge) = edge_rx.recv().await {
+        if !edge.high
+            && let Err(err) = engage("e-stop pin", &manager_channel_map).await
+        {
+            error!("Failed to halt Maestro; error={:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackpressurePolicy;
+    use crate::managers::queue::TaskQueue;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn halting_rejects_queued_motion_tasks() {
+        let queue = Arc::new(TaskQueue::new("MAESTRO", 4, BackpressurePolicy::Block));
+        let (resp_tx, close_fist) = oneshot::channel();
+        queue
+            .send(ManagerChannelData {
+                task_code: "CLOSE_FIST".to_string(),
+                task_data: None,
+                priority: Priority::Normal,
+                source: None,
+                resp_tx,
+            })
+            .await
+            .unwrap();
+        let manager_channel_map: ManagerChannelMap =
+            HashMap::from([("MAESTRO".to_string(), queue.clone())]);
+        // Stands in for the Maestro manager, which sees the halt task and nothing else
+        let manager = async {
+            let data = queue.recv().await;
+            assert!(matches!(
+                data.task_code.as_str(),
+                FREEZE_TASK | RELEASE_TASK
+            ));
+            data.resp_tx.send("halted".to_string()).unwrap();
+        };
+        let (halted, _) = tokio::join!(halt(&manager_channel_map), manager);
+
+        assert_eq!(halted.unwrap(), "halted");
+        assert_eq!(close_fist.await.unwrap(), rejection());
+        assert_eq!(queue.len(), 0);
+    }
+}
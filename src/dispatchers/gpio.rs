@@ -1,6 +1,253 @@
-#[cfg(feature = "pi")]
-mod actual;
-#[cfg(not(feature = "pi"))]
-mod mock;
+// This files defines a function that monitors GPIO pins and dispatches SGCP requests based on how
+// the buttons wired to them are pressed. This is an alternate strategy of dispatching commands than
+// the SGCP-based commands sent over TCP. We usually use this for testing the arm with a button to
+// control it.
+mod press;
+
+use super::Dispatcher;
+use super::dispatch_task;
+use crate::ManagerChannelMap;
+use crate::config::ButtonConfig;
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
+use crate::config::GpioMonitorConfig;
+use crate::managers::registry::ResourceRegistry;
+use crate::resources::common::gpio::Edge;
+use crate::resources::common::gpio::Gpio;
+use crate::resources::common::gpio::GpioInterface;
+use crate::sgcp;
+use log::*;
+use press::PressDetector;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio::time::sleep_until;
 
 pub struct GpioDispatcher;
+
+impl Dispatcher for GpioDispatcher {
+    async fn run(manager_channel_map: ManagerChannelMap) {
+        let gpio_monitor_config = Config::global()
+            .dispatcher
+            .gpio_monitor
+            .as_ref()
+            .expect("Expected GPIO monitor config to be defined");
+
+        let gpio = Gpio::new().expect("Failed to initialize GPIO");
+
+        #[cfg(not(feature = "pi"))]
+        if let Some(path) = &gpio_monitor_config.simulator_socket {
+            let simulator = gpio.clone();
+            tokio::spawn(async move {
+                if let Err(err) = simulator.serve(path).await {
+                    error!("GPIO simulator failed; error={:?}", err);
+                }
+            });
+        }
+
+        monitor(gpio_monitor_config, gpio, manager_channel_map).await;
+    }
+}
+
+/// Watches every configured button until their monitors exit
+async fn monitor(
+    config: &'static GpioMonitorConfig,
+    mut gpio: impl GpioInterface,
+    manager_channel_map: ManagerChannelMap,
+) {
+    let mut monitors = JoinSet::new();
+    for button in &config.buttons {
+        let (edge_tx, edge_rx) = unbounded_channel();
+        if let Err(err) = gpio.watch(button.pin, edge_tx) {
+            error!("Failed to watch pin {:?}; error={:?}", button.pin, err);
+            continue;
+        }
+        info!("Started GPIO pin monitor for pin {:?}", button.pin);
+
+        monitors.spawn(monitor_button(
+            config,
+            button,
+            edge_rx,
+            manager_channel_map.clone(),
+        ));
+    }
+
+    // `gpio` has to stay alive while the monitors run, dropping it stops the edges
+    while monitors.join_next().await.is_some() {}
+}
+
+/// Detects presses on a single button and dispatches the request bound to each one
+async fn monitor_button(
+    config: &'static GpioMonitorConfig,
+    button: &'static ButtonConfig,
+    mut edges: UnboundedReceiver<Edge>,
+    manager_channel_map: ManagerChannelMap,
+) {
+    let mut detector = PressDetector::new(config);
+
+    loop {
+        let press = match detector.deadline() {
+            Some(deadline) => tokio::select! {
+                edge = edges.recv() => match edge {
+                    Some(edge) => {
+                        detector.on_edge(edge);
+                        None
+                    },
+                    None => break,
+                },
+                _ = sleep_until(deadline) => detector.on_timeout(Instant::now()),
+            },
+            None => match edges.recv().await {
+                Some(edge) => {
+                    detector.on_edge(edge);
+                    None
+                },
+                None => break,
+            },
+        };
+
+        let Some(press) = press else {
+            continue;
+        };
+        info!("Detected {:?} press on pin {:?}", press, button.pin);
+
+        for binding in button.bindings.iter().filter(|b| b.press == press) {
+            let Some(resource) = ResourceRegistry::global()
+                .lookup(&binding.resource)
+                .map(|registration| registration.resource)
+            else {
+                error!(
+                    "Unknown resource {:?} bound to pin {:?}",
+                    binding.resource, button.pin
+                );
+                continue;
+            };
+            let request = sgcp::Request {
+                resource: resource as i32,
+                task_code: binding.task_code.clone(),
+                task_data: None,
+            };
+
+            match dispatch_task(request, CommandDispatchStrategy::Gpio, &manager_channel_map).await
+            {
+                Ok(res) => info!("Receieved response from {:?} manager: {:?}", resource, res),
+                Err(e) => error!("Failed to dispatch {:?} task: {:?}", resource, e),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "pi")))]
+mod tests {
+    use super::*;
+    use crate::config::BackpressurePolicy;
+    use crate::managers::queue::TaskQueue;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::sleep;
+    use tokio::time::timeout;
+
+    const PIN: u8 = 2;
+
+    fn config() -> &'static GpioMonitorConfig {
+        let config = toml::from_str(
+            r#"
+            debounce_ms = 5
+            long_press_ms = 200
+            double_press_window_ms = 60
+
+            [[buttons]]
+            pin = 2
+            bindings = [
+                { press = "short", resource = "MAESTRO", task_code = "CLOSE_FIST" },
+                { press = "long", resource = "MAESTRO", task_code = "OPEN_FIST" },
+                { press = "double", resource = "EMG", task_code = "CALIBRATE" },
+            ]
+            "#,
+        )
+        .unwrap();
+        Box::leak(Box::new(config))
+    }
+
+    /// Starts monitoring simulated pins, returning the queues of the Maestro and EMG managers
+    async fn start(gpio: &Gpio) -> (Arc<TaskQueue>, Arc<TaskQueue>) {
+        let maestro_rx = Arc::new(TaskQueue::new("MAESTRO", 8, BackpressurePolicy::Block));
+        let emg_rx = Arc::new(TaskQueue::new("EMG", 8, BackpressurePolicy::Block));
+        let map = HashMap::from([
+            (
+                sgcp::Resource::Maestro.as_str_name().to_string(),
+                maestro_rx.clone(),
+            ),
+            (
+                sgcp::Resource::Emg.as_str_name().to_string(),
+                emg_rx.clone(),
+            ),
+        ]);
+        tokio::spawn(monitor(config(), gpio.clone(), map));
+        // Let the monitor register its watchers
+        sleep(Duration::from_millis(20)).await;
+        (maestro_rx, emg_rx)
+    }
+
+    async fn press(gpio: &Gpio, hold: Duration) {
+        gpio.set_input(PIN, false);
+        sleep(hold).await;
+        gpio.set_input(PIN, true);
+    }
+
+    async fn next_task(rx: &TaskQueue) -> String {
+        let data = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("Expected a task to be dispatched");
+        data.resp_tx.send("ok".to_string()).unwrap();
+        data.task_code
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn short_press_dispatches_its_binding() {
+        let gpio = Gpio::default();
+        let (maestro_rx, _emg_rx) = start(&gpio).await;
+        press(&gpio, Duration::from_millis(30)).await;
+        assert_eq!(next_task(&maestro_rx).await, "CLOSE_FIST");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn long_press_fires_while_the_button_is_held() {
+        let gpio = Gpio::default();
+        let (maestro_rx, _emg_rx) = start(&gpio).await;
+        gpio.set_input(PIN, false);
+        assert_eq!(next_task(&maestro_rx).await, "OPEN_FIST");
+        gpio.set_input(PIN, true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn double_press_dispatches_only_the_double_binding() {
+        let gpio = Gpio::default();
+        let (maestro_rx, emg_rx) = start(&gpio).await;
+        press(&gpio, Duration::from_millis(20)).await;
+        sleep(Duration::from_millis(20)).await;
+        press(&gpio, Duration::from_millis(20)).await;
+        assert_eq!(next_task(&emg_rx).await, "CALIBRATE");
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(maestro_rx.len(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn contact_bounce_is_ignored() {
+        let gpio = Gpio::default();
+        let (maestro_rx, _emg_rx) = start(&gpio).await;
+        // Bounces shorter than the debounce interval on press and release
+        gpio.set_input(PIN, false);
+        gpio.set_input(PIN, true);
+        gpio.set_input(PIN, false);
+        sleep(Duration::from_millis(30)).await;
+        gpio.set_input(PIN, true);
+        gpio.set_input(PIN, false);
+        gpio.set_input(PIN, true);
+        assert_eq!(next_task(&maestro_rx).await, "CLOSE_FIST");
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(maestro_rx.len(), 0);
+    }
+}
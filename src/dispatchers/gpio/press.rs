@@ -0,0 +1,171 @@
+// Debounces the edges of a single button and turns them into short, long and double presses
+use crate::config::GpioMonitorConfig;
+use crate::config::PressPattern;
+use crate::resources::common::gpio::Edge;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    /// Button is down. `repeat` is set if this is the second press of a potential double press.
+    Pressed {
+        since: Instant,
+        repeat: bool,
+    },
+    /// Button was held past the long press threshold and has not been released yet
+    Held,
+    /// A short press ended and a second press may still turn it into a double press
+    Released {
+        at: Instant,
+    },
+}
+
+pub struct PressDetector {
+    debounce: Duration,
+    long_press: Duration,
+    double_press_window: Duration,
+    state: State,
+    /// Debounced level, `true` while the button is down
+    pressed: bool,
+    /// Raw level after the last edge and when it arrived. It becomes the debounced level once no
+    /// other edge follows it within `debounce`.
+    settling: Option<(bool, Instant)>,
+}
+
+impl PressDetector {
+    pub fn new(config: &GpioMonitorConfig) -> Self {
+        PressDetector {
+            debounce: Duration::from_millis(config.debounce_ms),
+            long_press: Duration::from_millis(config.long_press_ms),
+            double_press_window: Duration::from_millis(config.double_press_window_ms),
+            state: State::Idle,
+            pressed: false,
+            settling: None,
+        }
+    }
+
+    /// Time at which `on_timeout` should be called if no edge arrives before then
+    pub fn deadline(&self) -> Option<Instant> {
+        let settled_at = self.settling.map(|(_, at)| at + self.debounce);
+        let press_deadline = match self.state {
+            State::Pressed { since, .. } => Some(since + self.long_press),
+            State::Released { at } => Some(at + self.double_press_window),
+            State::Idle | State::Held => None,
+        };
+        match (settled_at, press_deadline) {
+            (Some(settled_at), Some(press_deadline)) => Some(settled_at.min(press_deadline)),
+            (settled_at, press_deadline) => settled_at.or(press_deadline),
+        }
+    }
+
+    /// Feeds a raw edge into the detector. Software debounce: the level only counts once it has
+    /// held for the debounce interval, so every edge restarts the interval.
+    pub fn on_edge(&mut self, edge: Edge) {
+        self.settling = Some((!edge.high, edge.at));
+    }
+
+    /// Resolves whatever was due by `now`: a level that has settled, or the pending press once its
+    /// deadline has passed. Returns the press this completes (if any).
+    pub fn on_timeout(&mut self, now: Instant) -> Option<PressPattern> {
+        if let Some((pressed, at)) = self.settling
+            && now >= at + self.debounce
+        {
+            self.settling = None;
+            if pressed == self.pressed {
+                return None;
+            }
+            self.pressed = pressed;
+            return self.on_level(pressed, at);
+        }
+
+        let (state, press) = match self.state {
+            State::Pressed { since, .. } if now >= since + self.long_press => {
+                (State::Held, Some(PressPattern::Long))
+            },
+            State::Released { at } if now >= at + self.double_press_window => {
+                (State::Idle, Some(PressPattern::Short))
+            },
+            state => (state, None),
+        };
+        self.state = state;
+        press
+    }
+
+    /// Moves on from a debounced level change at `at`. Buttons are active low, so a falling edge
+    /// is a press.
+    fn on_level(&mut self, pressed: bool, at: Instant) -> Option<PressPattern> {
+        let (state, press) = match (self.state, pressed) {
+            (State::Idle, true) => (
+                State::Pressed {
+                    since: at,
+                    repeat: false,
+                },
+                None,
+            ),
+            (State::Released { .. }, true) => (
+                State::Pressed {
+                    since: at,
+                    repeat: true,
+                },
+                None,
+            ),
+            (State::Pressed { repeat: true, .. }, false) => {
+                (State::Idle, Some(PressPattern::Double))
+            },
+            (State::Pressed { repeat: false, .. }, false) => (State::Released { at }, None),
+            (State::Held, false) => (State::Idle, None),
+            (state, _) => (state, None),
+        };
+        self.state = state;
+        press
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> PressDetector {
+        let config = toml::from_str(
+            r#"
+            debounce_ms = 5
+            long_press_ms = 200
+            double_press_window_ms = 60
+            buttons = []
+            "#,
+        )
+        .unwrap();
+        PressDetector::new(&config)
+    }
+
+    fn edge(high: bool, at: Instant) -> Edge {
+        Edge { high, at }
+    }
+
+    /// Runs the detector's timers up to `until`, collecting the presses they complete
+    fn run_until(detector: &mut PressDetector, until: Instant) -> Vec<PressPattern> {
+        let mut presses = Vec::new();
+        while let Some(deadline) = detector.deadline().filter(|&deadline| deadline <= until) {
+            presses.extend(detector.on_timeout(deadline));
+        }
+        presses
+    }
+
+    #[test]
+    fn release_bouncing_within_the_debounce_interval_still_ends_the_press() {
+        let mut detector = detector();
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+
+        detector.on_edge(edge(false, ms(0)));
+        assert!(run_until(&mut detector, ms(50)).is_empty());
+        detector.on_edge(edge(true, ms(50)));
+        assert!(run_until(&mut detector, ms(56)).is_empty());
+        // A bounce, followed 2ms later by the real release
+        detector.on_edge(edge(false, ms(56)));
+        detector.on_edge(edge(true, ms(58)));
+
+        assert_eq!(run_until(&mut detector, ms(1000)), [PressPattern::Short]);
+    }
+}
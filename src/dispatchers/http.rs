@@ -0,0 +1,381 @@
+// HTTP/JSON front end to `dispatch_task` for clients that would rather not speak length-prefixed
+// protobuf. Tasks are posted to `/v1/tasks/{resource}/{task_code}` with an optional JSON encoded
+// `sgcp::Request` body carrying the task data, i.e. `{"maestro_data": {...}}`. The resource and
+// task code are always taken from the path, and `?deadline_ms=N` overrides the task deadline.
+use super::DispatchError;
+use super::Dispatcher;
+use super::dispatch_task_with_deadline;
+use crate::ManagerChannelMap;
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
+use crate::managers::TASK_ERROR_PREFIX;
+use crate::managers::registry::ResourceRegistry;
+use crate::sgcp;
+use anyhow::Error;
+use http_body_util::BodyExt;
+use http_body_util::Full;
+use http_body_util::Limited;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use hyper::body::Body;
+use hyper::body::Bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use log::*;
+use serde_json::Value;
+use serde_json::json;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const TASKS_PATH_PREFIX: &str = "/v1/tasks/";
+
+pub struct HttpDispatcher;
+
+impl Dispatcher for HttpDispatcher {
+    /// Starts the HTTP listener loop
+    async fn run(manager_channel_map: ManagerChannelMap) {
+        let server_config = Config::global()
+            .dispatcher
+            .http
+            .as_ref()
+            .expect("Expected http dispatcher config to be defined");
+
+        let listener = TcpListener::bind(server_config.address.clone())
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Couldn't bind to address {}: {}", server_config.address, e)
+            });
+        info!("GPM HTTP API listening on {:?}", server_config.address);
+
+        let manager_channel_map = Arc::new(manager_channel_map);
+        loop {
+            let (stream, client_addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    error!(
+                        "Encountered an error when accepting new connection; error={:?}",
+                        err
+                    );
+                    continue;
+                },
+            };
+            debug!("Accepted new HTTP connection from host={:?}", client_addr);
+
+            let manager_channel_map = manager_channel_map.clone();
+            tokio::spawn(async move {
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(
+                        TokioIo::new(stream),
+                        service_fn(|req| handle_request(req, &manager_channel_map)),
+                    )
+                    .await
+                {
+                    error!("Error serving HTTP connection; error={:?}", err);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_request<B>(
+    req: Request<B>,
+    manager_channel_map: &ManagerChannelMap,
+) -> Result<Response<Full<Bytes>>, Infallible>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let Some((resource, task_code)) = req
+        .uri()
+        .path()
+        .strip_prefix(TASKS_PATH_PREFIX)
+        .and_then(|rest| rest.split_once('/'))
+        .map(|(resource, task_code)| (resource.to_uppercase(), task_code.to_uppercase()))
+    else {
+        return Ok(error_response(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "Expected a path of the form /v1/tasks/{resource}/{task_code}",
+        ));
+    };
+    if req.method() != Method::POST {
+        return Ok(error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            "Tasks must be submitted with POST",
+        ));
+    }
+    let Some(registration) = ResourceRegistry::global().lookup(&resource) else {
+        return Ok(error_response(
+            StatusCode::NOT_FOUND,
+            DispatchError::UnknownResource.kind(),
+            &format!("Unknown resource {:?}", resource),
+        ));
+    };
+
+    let deadline = match requested_deadline(&req) {
+        Ok(deadline) => deadline,
+        Err(err) => {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                &err.to_string(),
+            ));
+        },
+    };
+    let mut request = match read_request(req).await {
+        Ok(request) => request,
+        Err(err) => {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                &err.to_string(),
+            ));
+        },
+    };
+    request.set_resource(registration.resource);
+    request.task_code = task_code.clone();
+
+    info!("Received HTTP request: {:?}", request);
+    let (status, body) = match dispatch_task_with_deadline(
+        request,
+        CommandDispatchStrategy::Http,
+        manager_channel_map,
+        deadline,
+    )
+    .await
+    {
+        Ok(res) if res.starts_with(TASK_ERROR_PREFIX) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            json!({
+                "ok": false,
+                "resource": resource,
+                "task_code": task_code,
+                "error": { "kind": "task_error", "message": res },
+            }),
+        ),
+        Ok(res) => (
+            StatusCode::OK,
+            json!({
+                "ok": true,
+                "resource": resource,
+                "task_code": task_code,
+                "response": res,
+            }),
+        ),
+        Err(err) => {
+            error!("An error occurred when dispatching task; error={:?}", err);
+            let (status, kind) = classify(&err);
+            (
+                status,
+                json!({
+                    "ok": false,
+                    "resource": resource,
+                    "task_code": task_code,
+                    "error": { "kind": kind, "message": err.to_string() },
+                }),
+            )
+        },
+    };
+    Ok(json_response(status, &body))
+}
+
+/// Reads the `?deadline_ms=N` override of the resource's configured task deadline
+fn requested_deadline<B>(req: &Request<B>) -> Result<Option<Duration>, Error> {
+    let Some(deadline_ms) = req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("deadline_ms="))
+    }) else {
+        return Ok(None);
+    };
+    match deadline_ms.parse() {
+        Ok(deadline_ms) if deadline_ms > 0 => Ok(Some(Duration::from_millis(deadline_ms))),
+        _ => Err(Error::msg(format!(
+            "deadline_ms must be a positive number of milliseconds, got {:?}",
+            deadline_ms
+        ))),
+    }
+}
+
+/// Decodes the optional JSON body, an empty body is a request without task data
+async fn read_request<B>(req: Request<B>) -> Result<sgcp::Request, Error>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let max_body_size = Config::current()
+        .dispatcher
+        .http
+        .as_ref()
+        .map_or(usize::MAX, |http| http.max_body_size_in_bytes);
+    let body = Limited::new(req.into_body(), max_body_size)
+        .collect()
+        .await
+        .map_err(|e| Error::msg(format!("Failed to read request body: {}", e)))?
+        .to_bytes();
+
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(sgcp::Request::default());
+    }
+    serde_json::from_slice(&body).map_err(|e| Error::msg(format!("Invalid request body: {}", e)))
+}
+
+fn classify(err: &Error) -> (StatusCode, &'static str) {
+    match err.downcast_ref::<DispatchError>() {
+        Some(dispatch_error @ (DispatchError::EstopEngaged | DispatchError::Overridden { .. })) => {
+            (StatusCode::CONFLICT, dispatch_error.kind())
+        },
+        Some(
+            dispatch_error @ (DispatchError::UnknownResource | DispatchError::UnknownTask { .. }),
+        ) => (StatusCode::NOT_FOUND, dispatch_error.kind()),
+        Some(dispatch_error @ DispatchError::MismatchedTaskData(_)) => {
+            (StatusCode::BAD_REQUEST, dispatch_error.kind())
+        },
+        Some(dispatch_error @ DispatchError::Timeout { .. }) => {
+            (StatusCode::GATEWAY_TIMEOUT, dispatch_error.kind())
+        },
+        Some(dispatch_error) => (StatusCode::SERVICE_UNAVAILABLE, dispatch_error.kind()),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, "other"),
+    }
+}
+
+fn error_response(status: StatusCode, kind: &str, message: &str) -> Response<Full<Bytes>> {
+    json_response(
+        status,
+        &json!({ "ok": false, "error": { "kind": kind, "message": message } }),
+    )
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn post(uri: &str, body: impl Into<Bytes>) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .body(Full::new(body.into()))
+            .unwrap()
+    }
+
+    async fn send(req: Request<Full<Bytes>>) -> (StatusCode, Value) {
+        let res = handle_request(req, &HashMap::new()).await.unwrap();
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn maps_dispatch_errors_to_status_codes() {
+        let status = |err: DispatchError| classify(&err.into());
+        assert_eq!(
+            status(DispatchError::EstopEngaged),
+            (StatusCode::CONFLICT, "estop_engaged")
+        );
+        assert_eq!(
+            status(DispatchError::Overridden {
+                holder: CommandDispatchStrategy::Tcp,
+                source: CommandDispatchStrategy::Http,
+            }),
+            (StatusCode::CONFLICT, "overridden")
+        );
+        assert_eq!(
+            status(DispatchError::UnknownTask {
+                resource: "MAESTRO",
+                task_code: "WAVE".to_string(),
+            }),
+            (StatusCode::NOT_FOUND, "unknown_task")
+        );
+        assert_eq!(
+            status(DispatchError::MismatchedTaskData("MAESTRO")),
+            (StatusCode::BAD_REQUEST, "mismatched_task_data")
+        );
+        assert_eq!(
+            status(DispatchError::Timeout {
+                resource: "MAESTRO",
+                deadline: Duration::from_millis(10),
+            }),
+            (StatusCode::GATEWAY_TIMEOUT, "timeout")
+        );
+        assert_eq!(
+            status(DispatchError::QueueFull("MAESTRO")),
+            (StatusCode::SERVICE_UNAVAILABLE, "queue_full")
+        );
+        assert_eq!(
+            classify(&Error::msg("unexpected")),
+            (StatusCode::INTERNAL_SERVER_ERROR, "other")
+        );
+    }
+
+    #[test]
+    fn parses_the_requested_deadline() {
+        let deadline = |uri: &str| requested_deadline(&post(uri, ""));
+        assert_eq!(deadline("/v1/tasks/bms/get_health_metrics").unwrap(), None);
+        assert_eq!(
+            deadline("/v1/tasks/bms/get_health_metrics?verbose=1&deadline_ms=250").unwrap(),
+            Some(Duration::from_millis(250))
+        );
+        assert!(deadline("/v1/tasks/bms/get_health_metrics?deadline_ms=0").is_err());
+        assert!(deadline("/v1/tasks/bms/get_health_metrics?deadline_ms=soon").is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_requests_with_a_json_error() {
+        let (status, body) =
+            send(post("/v1/tasks/bms/get_health_metrics?deadline_ms=-1", "")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["ok"], false);
+        assert_eq!(body["error"]["kind"], "invalid_request");
+
+        let (status, body) = send(post("/v1/tasks/wrist/rotate", "")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["kind"], "unknown_resource");
+
+        // Bodies over the configured limit are refused before they are decoded
+        let max_body_size = Config::current()
+            .dispatcher
+            .http
+            .as_ref()
+            .unwrap()
+            .max_body_size_in_bytes;
+        let oversized = vec![b' '; max_body_size + 1];
+        let (status, body) = send(post("/v1/tasks/bms/get_health_metrics", oversized)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["kind"], "invalid_request");
+    }
+
+    #[tokio::test]
+    async fn reports_dispatch_errors_with_the_task() {
+        // No managers are running, so the task can't be queued
+        let (status, body) = send(post("/v1/tasks/bms/get_health_metrics", "")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            body,
+            json!({
+                "ok": false,
+                "resource": "BMS",
+                "task_code": "GET_HEALTH_METRICS",
+                "error": {
+                    "kind": "not_initialized",
+                    "message": DispatchError::NotInitialized("BMS").to_string(),
+                },
+            })
+        );
+    }
+}
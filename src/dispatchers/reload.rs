@@ -0,0 +1,139 @@
+// Config reloads, triggered by the config files changing on disk or an SGCP `RELOAD_CONFIG`
+// request for any resource. Only runtime tunable settings can be reloaded (see `Config::reload`);
+// once a reload is applied every resource manager is told to pick up the new settings through its
+// channel. The files are polled rather than watched with inotify to keep working on filesystems
+// without it.
+use crate::ManagerChannelMap;
+use crate::config::Config;
+use crate::journal;
+use crate::journal::Event;
+use crate::managers::ManagerChannelData;
+use crate::managers::RELOAD_CONFIG_TASK;
+use crate::managers::queue::Priority;
+use anyhow::Error;
+use anyhow::Result;
+use log::*;
+use std::fs;
+use std::time::Duration;
+use std::time::SystemTime;
+use tokio::sync::oneshot;
+use tokio::time::MissedTickBehavior;
+use tokio::time::timeout;
+
+/// Reloads the config and notifies the resource managers
+pub async fn reload(trigger: &str, manager_channel_map: &ManagerChannelMap) -> Result<String> {
+    let changes = Config::reload().inspect_err(|err| {
+        warn!(
+            "Config reload requested by {} failed; error={}",
+            trigger, err
+        );
+    })?;
+    if changes.is_empty() {
+        info!("Config reload requested by {}; nothing changed", trigger);
+        return Ok("Config unchanged".to_string());
+    }
+    info!("Config reloaded by {}; changed keys={:?}", trigger, changes);
+    journal::record(Event::ConfigReloaded {
+        keys: changes.clone(),
+    });
+
+    notify_managers(manager_channel_map).await?;
+    Ok(format!("Applied config changes: {}", changes.join(", ")))
+}
+
+/// Tells every resource manager to pick up the new settings, giving each until its task deadline.
+/// Returns an `Err` listing the managers that didn't.
+async fn notify_managers(manager_channel_map: &ManagerChannelMap) -> Result<()> {
+    let config = Config::current();
+    let mut failed = Vec::new();
+    for (resource, tx) in manager_channel_map {
+        let (resp_tx, resp_rx) = oneshot::channel::<String>();
+        let notified = async {
+            tx.send(ManagerChannelData {
+                task_code: RELOAD_CONFIG_TASK.to_string(),
+                task_data: None,
+                priority: Priority::High,
+                source: None,
+                resp_tx,
+            })
+            .await?;
+            resp_rx
+                .await
+                .map_err(|e| Error::msg(format!("No response: {:?}", e)))
+        };
+        match timeout(config.task_deadline(resource), notified).await {
+            Ok(Ok(res)) => debug!("{} manager reloaded config; response={:?}", resource, res),
+            Ok(Err(err)) => {
+                warn!("{} manager didn't reload config; error={}", resource, err);
+                failed.push(resource.as_str());
+            },
+            Err(_) => {
+                warn!("{} manager didn't reload config in time", resource);
+                failed.push(resource.as_str());
+            },
+        }
+    }
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+    failed.sort();
+    Err(Error::msg(format!(
+        "Config reloaded, but these managers didn't pick it up: {}",
+        failed.join(", ")
+    )))
+}
+
+/// Reloads the config whenever one of its files is created, modified or removed
+pub async fn watch_files(poll_interval: Duration, manager_channel_map: ManagerChannelMap) {
+    let paths = Config::source_paths();
+    let modified_times = || -> Vec<Option<SystemTime>> {
+        paths
+            .iter()
+            .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    };
+    info!("Watching {:?} for config changes", paths);
+
+    let mut last_modified = modified_times();
+    let mut interval = tokio::time::interval(poll_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let modified = modified_times();
+        if modified != last_modified {
+            last_modified = modified;
+            // Errors are logged by `reload`, the previous config stays in effect
+            let _ = reload("config file change", &manager_channel_map).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackpressurePolicy;
+    use crate::managers::queue::TaskQueue;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_managers_that_do_not_reload() {
+        let served = Arc::new(TaskQueue::new("BMS", 1, BackpressurePolicy::Block));
+        // Nothing serves this one, so it never responds
+        let hung = Arc::new(TaskQueue::new("EMG", 1, BackpressurePolicy::Block));
+        let manager_channel_map: ManagerChannelMap = HashMap::from([
+            ("BMS".to_string(), served.clone()),
+            ("EMG".to_string(), hung),
+        ]);
+        tokio::spawn(async move {
+            loop {
+                let data = served.recv().await;
+                let _ = data.resp_tx.send("reloaded".to_string());
+            }
+        });
+
+        let err = notify_managers(&manager_channel_map).await.unwrap_err();
+        assert!(err.to_string().ends_with(": EMG"));
+    }
+}
@@ -0,0 +1,193 @@
+// Graceful shutdown. Once a signal arrives, or every dispatcher has exited, new tasks are
+// rejected, the tasks already dispatched are given time to finish, the hand is moved to a rest
+// pose and a final metrics snapshot is pushed, all within `shutdown.deadline_ms`. The journal is
+// flushed last.
+use crate::ManagerChannelMap;
+use crate::config::Config;
+use crate::dispatchers::error::DispatchError;
+use crate::dispatchers::estop;
+use crate::journal;
+use crate::journal::Event;
+use crate::managers::ManagerChannelData;
+use crate::managers::TASK_ERROR_PREFIX;
+use crate::managers::queue::Priority;
+use crate::sgcp;
+use crate::telemetry::push;
+use anyhow::Error;
+use anyhow::Result;
+use log::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::signal::unix::SignalKind;
+use tokio::signal::unix::signal;
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+use tokio::time::timeout;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Rejects tasks once shutdown has started
+pub fn check() -> Result<(), DispatchError> {
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        Err(DispatchError::ShuttingDown)
+    } else {
+        Ok(())
+    }
+}
+
+/// Counts a task as in flight until dropped
+pub struct InFlight(());
+
+impl InFlight {
+    pub fn start() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        InFlight(())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Resolves with the name of the first SIGTERM or SIGINT received. Never resolves if the signal
+/// handlers can't be installed.
+pub async fn wait_for_signal() -> &'static str {
+    let (mut sigterm, mut sigint) = match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(sigterm), Ok(sigint)) => (sigterm, sigint),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("Failed to install signal handlers; error={:?}", err);
+            return std::future::pending().await;
+        },
+    };
+    tokio::select! {
+        _ = sigterm.recv() => "SIGTERM",
+        _ = sigint.recv() => "SIGINT",
+    }
+}
+
+/// Stops taking tasks and leaves the hand at rest. `reason` is the signal received or why GPM
+/// can't carry on. Returns once it is safe to exit.
+pub async fn shutdown(reason: &str, manager_channel_map: &ManagerChannelMap) {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    warn!("Shutting down ({})", reason);
+    journal::record(Event::ShutdownStarted {
+        reason: reason.to_string(),
+    });
+
+    let config = &Config::current().shutdown;
+    let deadline = Duration::from_millis(config.deadline_ms);
+    let rest_pose = async {
+        drain().await;
+        match &config.rest_task {
+            Some(_) if estop::engaged() => {
+                warn!("Emergency stop is engaged; leaving the hand where it is")
+            },
+            Some(rest_task) => match rest(rest_task, manager_channel_map).await {
+                Ok(res) => info!("Moved to rest pose with {}; response={:?}", rest_task, res),
+                Err(err) => error!("Failed to move to rest pose; error={:?}", err),
+            },
+            None => (),
+        }
+    };
+    // The snapshot is spooled straight away, so it survives a push that doesn't finish in time
+    let stop = async { tokio::join!(rest_pose, push::flush()) };
+    if timeout(deadline, stop).await.is_err() {
+        error!(
+            "Shutdown did not finish within {:?}; exiting anyway",
+            deadline
+        );
+    }
+
+    journal::flush();
+    log::logger().flush();
+}
+
+/// Waits for every dispatched task to return
+async fn drain() {
+    let in_flight = IN_FLIGHT.load(Ordering::SeqCst);
+    if in_flight > 0 {
+        info!("Waiting for {} in-flight task(s)", in_flight);
+    }
+    while IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+        sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+/// Sends the rest task to the Maestro manager directly, the regular dispatch path is closed now.
+/// Motion tasks still queued are dropped first so none of them moves the hand out of the rest pose.
+async fn rest(task_code: &str, manager_channel_map: &ManagerChannelMap) -> Result<String> {
+    let maestro_tx = manager_channel_map
+        .get(sgcp::Resource::Maestro.as_str_name())
+        .ok_or(Error::msg("Maestro resource manager not initialized"))?;
+    for task in maestro_tx.drain_normal() {
+        warn!("Dropping queued {:?} Maestro task", task.task_code);
+        let _ = task.resp_tx.send(format!(
+            "{}: {}",
+            TASK_ERROR_PREFIX,
+            DispatchError::ShuttingDown
+        ));
+    }
+    let (resp_tx, resp_rx) = oneshot::channel::<String>();
+    maestro_tx
+        .send(ManagerChannelData {
+            task_code: task_code.to_string(),
+            task_data: None,
+            priority: Priority::High,
+            source: None,
+            resp_tx,
+        })
+        .await
+        .map_err(|e| Error::msg(format!("Failed to send rest task to Maestro: {:?}", e)))?;
+    resp_rx
+        .await
+        .map_err(|e| Error::msg(format!("Failed to read response from Maestro: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackpressurePolicy;
+    use crate::managers::queue::TaskQueue;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn rest_pose_is_not_followed_by_queued_motion() {
+        let queue = Arc::new(TaskQueue::new("MAESTRO", 4, BackpressurePolicy::Block));
+        let (resp_tx, close_fist) = oneshot::channel();
+        queue
+            .send(ManagerChannelData {
+                task_code: "CLOSE_FIST".to_string(),
+                task_data: None,
+                priority: Priority::Normal,
+                source: None,
+                resp_tx,
+            })
+            .await
+            .unwrap();
+        let manager_channel_map: ManagerChannelMap =
+            HashMap::from([("MAESTRO".to_string(), queue.clone())]);
+
+        // Stands in for the Maestro manager, which sees the rest task and nothing else
+        let manager = async {
+            let data = queue.recv().await;
+            assert_eq!(data.task_code, "OPEN_FIST");
+            data.resp_tx.send("rested".to_string()).unwrap();
+        };
+        let (rested, _) = tokio::join!(rest("OPEN_FIST", &manager_channel_map), manager);
+
+        assert_eq!(rested.unwrap(), "rested");
+        assert!(close_fist.await.unwrap().starts_with(TASK_ERROR_PREFIX));
+        assert_eq!(queue.len(), 0);
+    }
+}
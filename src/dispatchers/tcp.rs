@@ -3,6 +3,7 @@ pub mod connection;
 use super::Dispatcher;
 use super::dispatch_task;
 use crate::ManagerChannelMap;
+use crate::config::CommandDispatchStrategy;
 use crate::config::Config;
 use crate::retry;
 use anyhow::Result;
@@ -76,7 +77,7 @@ async fn handle_connection(stream: TcpStream, map: &ManagerChannelMap) -> Result
                 Some(req) => {
                     // Successfully read a frame
                     info!("Recieved request: {:?}", req);
-                    let res = match dispatch_task(req, &map).await {
+                    let res = match dispatch_task(req, CommandDispatchStrategy::Tcp, map).await {
                         Ok(res) => res,
                         Err(err) => {
                             error!("An error occurred when dispatching task; error={:?}", err);
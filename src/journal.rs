@@ -0,0 +1,203 @@
+// Black-box journal of significant events, kept on disk so there is something to look at after
+// the arm misbehaves in the field. Entries are JSON lines appended by a dedicated thread and
+// synced to disk before the next batch, so a power cut loses at most the batch being written.
+pub mod rotating;
+
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
+use crate::config::RotatingFileConfig;
+use crate::status::Grip;
+use crate::status::SystemState;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Utc;
+use log::*;
+use rotating::RotatingFile;
+use serde::Serialize;
+use std::fs;
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::thread;
+
+#[cfg(feature = "pi")]
+use crate::status::ElectrodeCalibration;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Routine polls, like the EMG dispatcher's `IDLE`, are only recorded when they fail
+    TaskDispatched {
+        source: CommandDispatchStrategy,
+        resource: String,
+        task_code: String,
+        /// `ok`, or the kind of failure as reported in the `tasks_failed` metric
+        outcome: String,
+    },
+    /// Covers faults and low battery, which surface as the `fault` and `low_battery` states
+    StateChanged {
+        from: SystemState,
+        to: SystemState,
+    },
+    /// The battery fell below `low_voltage`. Its recovery shows as the state leaving `low_battery`.
+    BatteryAlarm {
+        voltage: f32,
+        low_voltage: f32,
+    },
+    GripChanged {
+        grip: Grip,
+    },
+    #[cfg(feature = "pi")]
+    CalibrationChanged {
+        electrodes: Vec<ElectrodeCalibration>,
+    },
+    EstopEngaged {
+        trigger: String,
+    },
+    EstopReset,
+    ConfigReloaded {
+        keys: Vec<String>,
+    },
+    ManagerRestarted {
+        resource: String,
+        reason: String,
+    },
+    /// `reason` is the signal received, or why GPM couldn't carry on
+    ShutdownStarted {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: Event,
+}
+
+enum Message {
+    Append(Entry),
+    /// Answered once everything sent before it is on disk
+    Flush(mpsc::Sender<()>),
+}
+
+static JOURNAL: OnceLock<mpsc::Sender<Message>> = OnceLock::new();
+
+/// Opens the journal and starts the thread writing to it
+pub fn init(config: &RotatingFileConfig) -> Result<()> {
+    let file = RotatingFile::open(config)?;
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("journal".to_string())
+        .spawn(move || write_entries(file, rx))?;
+    let _ = JOURNAL.set(tx);
+    info!("Journaling events to {:?}", config.path);
+    Ok(())
+}
+
+/// Appends an event to the journal, if there is one. Doesn't wait for the disk.
+pub fn record(event: Event) {
+    if let Some(tx) = JOURNAL.get() {
+        let _ = tx.send(Message::Append(Entry {
+            at: Utc::now(),
+            event,
+        }));
+    }
+}
+
+/// Blocks until every event recorded so far is on disk
+pub fn flush() {
+    if let Some(tx) = JOURNAL.get() {
+        let (done_tx, done_rx) = mpsc::channel();
+        if tx.send(Message::Flush(done_tx)).is_ok() {
+            let _ = done_rx.recv();
+        }
+    }
+}
+
+/// Returns up to `limit` of the latest entries, oldest first, or `None` if the journal isn't
+/// configured. Lines torn by a power cut are skipped.
+pub fn tail(limit: usize) -> Option<Result<Vec<serde_json::Value>>> {
+    let config = Config::global().journal.as_ref()?;
+    Some(read_tail(config, limit))
+}
+
+fn read_tail(config: &RotatingFileConfig, limit: usize) -> Result<Vec<serde_json::Value>> {
+    let mut entries = Vec::new();
+    for path in rotating::paths(config) {
+        let contents = fs::read_to_string(path)?;
+        let lines = contents
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str(line).ok());
+        entries.extend(lines.take(limit - entries.len()));
+        if entries.len() == limit {
+            break;
+        }
+    }
+    entries.reverse();
+    Ok(entries)
+}
+
+fn write_entries(mut file: RotatingFile, rx: mpsc::Receiver<Message>) {
+    while let Ok(message) = rx.recv() {
+        // Writes whatever else is already queued before paying for the sync
+        let mut acks = Vec::new();
+        for message in std::iter::once(message).chain(rx.try_iter()) {
+            match message {
+                Message::Append(entry) => {
+                    let mut line = serde_json::to_vec(&entry).unwrap_or_default();
+                    line.push(b'\n');
+                    if let Err(err) = file.append(&line) {
+                        error!("Failed to write journal entry; error={:?}", err);
+                    }
+                },
+                Message::Flush(ack) => acks.push(ack),
+            }
+        }
+        if let Err(err) = file.sync() {
+            error!("Failed to sync journal; error={:?}", err);
+        }
+        for ack in acks {
+            let _ = ack.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_latest_entries_across_files() {
+        let dir = std::env::temp_dir().join(format!("gpm-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = RotatingFileConfig {
+            path: dir.join("journal.jsonl"),
+            max_file_bytes: 200,
+            max_files: 3,
+        };
+
+        let mut file = RotatingFile::open(&config).unwrap();
+        for i in 0..5 {
+            let entry = Entry {
+                at: Utc::now(),
+                event: Event::ConfigReloaded {
+                    keys: vec![format!("key{}", i)],
+                },
+            };
+            let mut line = serde_json::to_vec(&entry).unwrap();
+            line.push(b'\n');
+            file.append(&line).unwrap();
+        }
+        file.append(b"{\"at\":\"torn").unwrap();
+
+        let keys: Vec<String> = read_tail(&config, 3)
+            .unwrap()
+            .iter()
+            .map(|entry| entry["keys"][0].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(keys, ["key2", "key3", "key4"]);
+        assert_eq!(read_tail(&config, 100).unwrap().len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+// Size-bounded append-only file shared by the journal and the log file
+use crate::config::RotatingFileConfig;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub struct RotatingFile {
+    path: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    /// Opens the file for appending, creating it and its directory if needed
+    pub fn open(config: &RotatingFileConfig) -> io::Result<Self> {
+        if let Some(dir) = parent_dir(&config.path) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = open_append(&config.path)?;
+        let len = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: config.path.clone(),
+            max_file_bytes: config.max_file_bytes,
+            max_files: config.max_files,
+            file,
+            len,
+        })
+    }
+
+    /// Appends `data` in a single write, rotating first if it would overflow the current file.
+    /// Nothing is synced to disk, see `sync`.
+    pub fn append(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.len > 0 && self.len + data.len() as u64 > self.max_file_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(data)?;
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    /// Waits for everything appended so far to reach the disk
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Shifts every file up by one, dropping the oldest, and starts a new file
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        for i in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, i - 1);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, i))?;
+            }
+        }
+        if self.max_files <= 1 {
+            fs::remove_file(&self.path)?;
+        }
+        self.file = open_append(&self.path)?;
+        self.len = 0;
+
+        // Persists the renames, otherwise a power cut can bring back the old names
+        if let Some(dir) = parent_dir(&self.path) {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.append(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Paths of the existing files in `config`, from the newest to the oldest
+pub fn paths(config: &RotatingFileConfig) -> Vec<PathBuf> {
+    (0..config.max_files)
+        .map(|i| rotated_path(&config.path, i))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// `journal.jsonl` becomes `journal.<n>.jsonl`, with `n = 0` being the file itself
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, n, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+fn parent_dir(path: &Path) -> Option<&Path> {
+    path.parent().filter(|dir| !dir.as_os_str().is_empty())
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_drops_the_oldest_file() {
+        let dir = std::env::temp_dir().join(format!("gpm-rotating-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = RotatingFileConfig {
+            path: dir.join("journal.jsonl"),
+            max_file_bytes: 10,
+            max_files: 3,
+        };
+
+        let mut file = RotatingFile::open(&config).unwrap();
+        for line in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            file.append(line.as_bytes()).unwrap();
+        }
+
+        let contents: Vec<String> = paths(&config)
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        assert_eq!(contents, ["dddddddd\n", "cccccccc\n", "bbbbbbbb\n"]);
+        assert_eq!(
+            paths(&config)[1],
+            dir.join("journal.1.jsonl"),
+            "rotated files keep their extension"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,23 +1,11 @@
 // A few handy macros used across the codebase
 
-/// Imports the protobuf generated code to enable de/serialization
-/// TODO: @krarpit this should really be a proc macro that reads from the
-///                sgcp folder
+/// Imports the protobuf generated code to enable de/serialization. The `sgcp` module and one
+/// submodule per resource are laid out by `build.rs` from the protos in the sgcp folder.
 #[macro_export]
 macro_rules! import_sgcp {
     () => {
-        pub mod sgcp {
-            include!(concat!(env!("OUT_DIR"), "/sgcp.rs"));
-            pub mod bms {
-                include!(concat!(env!("OUT_DIR"), "/sgcp.bms.rs"));
-            }
-            pub mod emg {
-                include!(concat!(env!("OUT_DIR"), "/sgcp.emg.rs"));
-            }
-            pub mod maestro {
-                include!(concat!(env!("OUT_DIR"), "/sgcp.maestro.rs"));
-            }
-        }
+        include!(concat!(env!("OUT_DIR"), "/_includes.rs"));
         use sgcp::*;
     };
 }
@@ -1,8 +1,11 @@
+mod cli;
 mod config;
 mod dispatchers;
+mod journal;
 mod macros;
 mod managers;
 mod resources;
+mod status;
 mod telemetry;
 
 use config::CommandDispatchStrategy;
@@ -10,51 +13,160 @@ use config::Config;
 use dispatchers::Dispatcher;
 use dispatchers::emg::EmgDispatcher;
 use dispatchers::gpio::GpioDispatcher;
+use dispatchers::http::HttpDispatcher;
 use dispatchers::tcp::TcpDispatcher;
 use log::*;
-use managers::HasMpscChannel;
-use managers::Manager;
-use managers::ManagerChannelData;
-use managers::ResourceManager;
-use resources::bms::Bms;
-use resources::emg::Emg;
-use resources::maestro::Maestro;
+use managers::queue::TaskQueue;
+use managers::registry::ResourceRegistry;
+use resources::common::gpio::Gpio;
+use status::FaultCause;
+use status::SystemState;
 use std::collections::HashMap;
-use tokio::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
 
-/// Represents the mapping between resource manager keys and the tx component
-/// of the resource manager's MPSC channel
-type ManagerChannelMap = HashMap<String, Sender<ManagerChannelData>>;
+/// Represents the mapping between resource manager keys and the queue the resource manager
+/// serves its tasks from
+type ManagerChannelMap = HashMap<String, Arc<TaskQueue>>;
 
 // Import protobuf definitions for task communication
 import_sgcp!();
 
 #[tokio::main]
 async fn main() {
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        },
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    // Commands other than `Run` only inspect the config, nothing is started
+    let output = match args.command {
+        cli::Command::Run => None,
+        cli::Command::CheckConfig => Some(cli::check_config(&args)),
+        cli::Command::DumpConfig => Some(cli::dump_config(&args)),
+    };
+    match output {
+        Some(Ok(output)) => {
+            println!("{}", output);
+            return;
+        },
+        Some(Err(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        },
+        None => (),
+    }
+
     #[cfg(feature = "dev")]
     console_subscriber::init(); // Used for Tokio runtime diagnostics
-    config::logger_init();
+
+    if let Err(err) = Config::init(&args) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    config::logger_init(args.log_level);
+
+    if let Some(journal_config) = &Config::global().journal
+        && let Err(err) = journal::init(journal_config)
+    {
+        error!("Failed to open the journal; error={:?}", err);
+    }
+
+    if let Some(status_config) = &Config::global().status {
+        match Gpio::new() {
+            Ok(gpio) => {
+                tokio::spawn(status::run_indicators(status_config, gpio));
+            },
+            Err(err) => error!("Failed to initialize status indicators; error={:?}", err),
+        }
+    }
 
     // Initialize resource managers and their communication channels.
-    let manager_channel_map = managers::macros::init_resource_managers! {
-        sgcp::Resource::Bms => Manager::<Bms>::new(),
-        sgcp::Resource::Emg => Manager::<Emg>::new(),
-        sgcp::Resource::Maestro => Manager::<Maestro>::new()
-    };
+    let manager_channel_map = ResourceRegistry::global().start_managers();
+
+    if let Some(pin) = Config::global().estop.pin {
+        match Gpio::new() {
+            Ok(gpio) => {
+                tokio::spawn(dispatchers::estop::monitor_pin(
+                    pin,
+                    gpio,
+                    manager_channel_map.clone(),
+                ));
+            },
+            Err(err) => error!("Failed to initialize e-stop pin; error={:?}", err),
+        }
+    }
+
+    if Config::global().bms.is_some() {
+        tokio::spawn(managers::bms::monitor_battery(manager_channel_map.clone()));
+    }
 
-    tokio::spawn(async {
-        let mut exporter = telemetry::Exporter::new();
+    let reload_config = &Config::global().reload;
+    if reload_config.watch {
+        tokio::spawn(dispatchers::reload::watch_files(
+            Duration::from_millis(reload_config.poll_interval_ms),
+            manager_channel_map.clone(),
+        ));
+    }
+
+    let telemetry_channel_map = manager_channel_map.clone();
+    tokio::spawn(async move {
+        let mut exporter = telemetry::Exporter::new(telemetry_channel_map);
         exporter.init().await
     });
 
     info!(
-        "Using {:?} as the command dispatch strategy",
+        "Using {:?} as the command dispatch strategies",
         Config::global().command_dispatch_strategy
     );
 
-    match Config::global().command_dispatch_strategy {
-        CommandDispatchStrategy::Tcp => TcpDispatcher::run(manager_channel_map).await,
-        CommandDispatchStrategy::Gpio => GpioDispatcher::run(manager_channel_map).await,
-        CommandDispatchStrategy::Emg => EmgDispatcher::run(manager_channel_map).await,
+    // Set before the dispatchers start so a calibration they request returns to it
+    status::set_state(SystemState::Ready);
+
+    // Every dispatcher shares the same resource managers
+    let mut dispatchers = JoinSet::new();
+    for strategy in &Config::global().command_dispatch_strategy {
+        let send_channel_map = manager_channel_map.clone();
+        match strategy {
+            CommandDispatchStrategy::Tcp => dispatchers.spawn(TcpDispatcher::run(send_channel_map)),
+            CommandDispatchStrategy::Http => {
+                dispatchers.spawn(HttpDispatcher::run(send_channel_map))
+            },
+            CommandDispatchStrategy::Gpio => {
+                dispatchers.spawn(GpioDispatcher::run(send_channel_map))
+            },
+            CommandDispatchStrategy::Emg => dispatchers.spawn(EmgDispatcher::run(send_channel_map)),
+        };
+    }
+
+    let shutdown_signal = dispatchers::shutdown::wait_for_signal();
+    tokio::pin!(shutdown_signal);
+    loop {
+        tokio::select! {
+            signal = &mut shutdown_signal => {
+                dispatchers::shutdown::shutdown(signal, &manager_channel_map).await;
+                std::process::exit(0);
+            },
+            result = dispatchers.join_next() => match result {
+                Some(Ok(_)) => warn!("A command dispatcher exited"),
+                Some(Err(err)) => {
+                    error!("A command dispatcher failed; error={:?}", err);
+                    status::raise_fault(FaultCause::Dispatcher);
+                },
+                None => {
+                    error!("Every command dispatcher exited");
+                    dispatchers::shutdown::shutdown("no dispatcher left", &manager_channel_map)
+                        .await;
+                    std::process::exit(1);
+                },
+            },
+        }
     }
 }
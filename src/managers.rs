@@ -2,41 +2,80 @@ pub mod bms;
 pub mod emg;
 pub mod macros;
 pub mod maestro;
+pub mod queue;
+pub mod registry;
+pub mod supervisor;
 
+use crate::config::CommandDispatchStrategy;
+use crate::config::Config;
 use crate::request::TaskData;
 use crate::resources::Resource;
+use crate::telemetry::tasks::TASK_METRICS;
 use anyhow::Result;
 use log::error;
 use log::info;
-use tokio::sync::mpsc::Receiver;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::mpsc::channel;
+use log::warn;
+use queue::Priority;
+use queue::TaskQueue;
+use std::sync::Arc;
 
 /// Represents the channel used by a resource manager to return the task response
 type Responder<T> = tokio::sync::oneshot::Sender<T>;
 
-// General resource manager configs
-const MAX_MPSC_CHANNEL_BUFFER: usize = 32;
-
 // Resource manager return values
 const TASK_SUCCESS: &str = "Successfully ran task";
+/// Prefix of the response sent back when a task fails
+pub const TASK_ERROR_PREFIX: &str = "Error";
+/// Internal task telling a manager to apply runtime tunable settings after a config reload
+pub const RELOAD_CONFIG_TASK: &str = "RELOAD_CONFIG";
 
 /// Represent a resource manager
-pub trait ResourceManager: HasMpscChannel {
+pub trait ResourceManager: HasTaskQueue {
     type ResourceType: Resource;
 
     async fn handle_task(&mut self, data: ManagerChannelData) -> Result<()>;
 
+    /// Applies runtime tunable settings from `Config::current`
+    fn reload_config(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     async fn run(&mut self) {
         info!(
             "{:?} resource manager now listening for messages",
             Self::ResourceType::name()
         );
-        while let Some(data) = self.rx().recv().await {
+        let queue = self.queue();
+        loop {
+            let data = queue.recv().await;
+            let resource = Self::ResourceType::name();
+            let task = data.task_code.clone();
+            let queue_depth = queue.len();
+            TASK_METRICS.set_queue_depth(&Self::ResourceType::name(), queue_depth);
+
+            // Nobody waits for the response once the dispatcher has timed out, so the task
+            // isn't carried out behind its back
+            if data.resp_tx.is_closed() {
+                warn!(
+                    resource = resource.as_str(), task = task.as_str();
+                    "Skipping {:?} task abandoned while queued", resource
+                );
+                continue;
+            }
+
+            if data.task_code == RELOAD_CONFIG_TASK {
+                let response = match self.reload_config() {
+                    Ok(_) => TASK_SUCCESS.to_string(),
+                    Err(e) => format!("Error: {e}"),
+                };
+                let _ = data.resp_tx.send(response);
+                continue;
+            }
             match self.handle_task(data).await {
                 Err(err) => error!(
+                    resource = resource.as_str(), task = task.as_str();
                     "Handling {:?} task failed with error={:?}",
-                    Self::ResourceType::name(),
+                    resource,
                     err
                 ),
                 _ => (),
@@ -45,41 +84,42 @@ pub trait ResourceManager: HasMpscChannel {
     }
 }
 
-pub trait HasMpscChannel {
-    fn tx(&self) -> Sender<ManagerChannelData>;
-    fn rx(&mut self) -> &mut Receiver<ManagerChannelData>;
+pub trait HasTaskQueue {
+    fn queue(&self) -> Arc<TaskQueue>;
 }
 
 /// Represents a resource manager
 pub struct Manager<S: Resource> {
-    pub tx: Sender<ManagerChannelData>,
-    pub rx: Receiver<ManagerChannelData>,
+    pub queue: Arc<TaskQueue>,
     resource: S,
 }
 
 impl<S: Resource> Manager<S> {
-    pub fn new() -> Self {
-        let (tx, rx) = channel(MAX_MPSC_CHANNEL_BUFFER);
+    /// Initializes the resource and attaches the manager to its task queue
+    pub fn new(queue: Arc<TaskQueue>) -> Self {
         Manager::<S> {
-            tx,
-            rx,
+            queue,
             resource: S::init(),
         }
     }
 }
 
-impl<S: Resource> HasMpscChannel for Manager<S> {
-    /// Returns tx component of the resource manager's MPSC channel to
-    /// enable sending tasks
-    fn tx(&self) -> Sender<ManagerChannelData> {
-        self.tx.clone()
+impl<S: Resource> HasTaskQueue for Manager<S> {
+    /// Returns the queue the resource manager serves its tasks from
+    fn queue(&self) -> Arc<TaskQueue> {
+        self.queue.clone()
     }
+}
 
-    /// Returns rx component of the resource manager's MPSC channel to
-    /// enable reading responses
-    fn rx(&mut self) -> &mut Receiver<ManagerChannelData> {
-        &mut self.rx
-    }
+/// Creates the queue used to send tasks to the given resource's manager. It outlives the manager
+/// so one restarted by the supervisor picks up the tasks queued for the one before it.
+pub fn manager_queue(resource: &'static str) -> Arc<TaskQueue> {
+    let config = Config::global().manager(resource);
+    Arc::new(TaskQueue::new(
+        resource,
+        config.buffer_size,
+        config.backpressure,
+    ))
 }
 
 /// Represents the format of messages that will be sent to each resource manager.
@@ -89,5 +129,70 @@ impl<S: Resource> HasMpscChannel for Manager<S> {
 pub struct ManagerChannelData {
     pub task_code: String,
     pub task_data: Option<TaskData>,
+    pub priority: Priority,
+    /// Dispatcher the task came from, `None` for tasks GPM raises itself
+    pub source: Option<CommandDispatchStrategy>,
     pub resp_tx: Responder<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackpressurePolicy;
+    use std::sync::Mutex;
+    use tokio::sync::oneshot;
+
+    /// Task codes handed to the stand-in manager below
+    static HANDLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Recorder;
+
+    impl Resource for Recorder {
+        fn init() -> Self {
+            Recorder
+        }
+
+        fn name() -> String {
+            "RECORDER".to_string()
+        }
+    }
+
+    impl ResourceManager for Manager<Recorder> {
+        type ResourceType = Recorder;
+
+        async fn handle_task(&mut self, data: ManagerChannelData) -> Result<()> {
+            HANDLED.lock().unwrap().push(data.task_code);
+            let _ = data.resp_tx.send(TASK_SUCCESS.to_string());
+            Ok(())
+        }
+    }
+
+    fn task(task_code: &str) -> (ManagerChannelData, oneshot::Receiver<String>) {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        let data = ManagerChannelData {
+            task_code: task_code.to_string(),
+            task_data: None,
+            priority: Priority::Normal,
+            source: None,
+            resp_tx,
+        };
+        (data, resp_rx)
+    }
+
+    #[tokio::test]
+    async fn skips_tasks_whose_dispatcher_gave_up() {
+        let queue = Arc::new(TaskQueue::new("RECORDER", 4, BackpressurePolicy::Block));
+        let (timed_out, resp_rx) = task("TIMED_OUT");
+        queue.send(timed_out).await.unwrap();
+        // The dispatcher stops waiting while the task is still queued
+        drop(resp_rx);
+        let (waited_on, resp_rx) = task("WAITED_ON");
+        queue.send(waited_on).await.unwrap();
+
+        let mut manager = Manager::<Recorder>::new(queue);
+        tokio::spawn(async move { manager.run().await });
+
+        assert_eq!(resp_rx.await.unwrap(), TASK_SUCCESS);
+        assert_eq!(*HANDLED.lock().unwrap(), ["WAITED_ON"]);
+    }
+}
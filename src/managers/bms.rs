@@ -1,17 +1,32 @@
-// All tasks operating on the BMS (Battery Management System) live in this file
+// All tasks operating on the BMS (Battery Management System) live in this file. Every reading of
+// the battery voltage raises or clears the low battery state, see `status::set_low_battery`.
+use crate::ManagerChannelMap;
+use crate::config::BmsConfig;
+use crate::config::Config;
+use crate::journal;
+use crate::journal::Event;
 use crate::managers::Manager;
 use crate::managers::ManagerChannelData;
 use crate::managers::ResourceManager;
 use crate::managers::TASK_SUCCESS;
 use crate::managers::macros::parse_channel_data;
+use crate::managers::queue::Priority;
+use crate::managers::registry;
+use crate::managers::registry::Registration;
 use crate::request::TaskData::BmsData;
 use crate::resources::bms::Bms;
+use crate::sgcp;
 use crate::sgcp::bms::*;
+use crate::status;
 use crate::todo;
 use anyhow::Error;
 use anyhow::Result;
 use anyhow::anyhow;
 use log::*;
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::sleep;
 
 impl ResourceManager for Manager<Bms> {
     type ResourceType = Bms;
@@ -19,12 +34,134 @@ impl ResourceManager for Manager<Bms> {
     async fn handle_task(&mut self, channel_data: ManagerChannelData) -> Result<()> {
         let (task, _, send_channel) =
             parse_channel_data!(channel_data, Task, BmsData).map_err(|e: Error| e)?;
-        match task {
-            Task::UndefinedTask => todo!(),
-            Task::GetHealthMetrics => todo!(),
-        }
+        let res = match task {
+            Task::UndefinedTask => {
+                todo!();
+                Ok(TASK_SUCCESS.to_string())
+            },
+            Task::GetHealthMetrics => self.health_metrics(),
+        };
+
+        let response = match res {
+            Ok(message) => message,
+            Err(e) => format!("Error: {e}"),
+        };
         Ok(send_channel
-            .send(TASK_SUCCESS.to_string())
+            .send(response)
             .map_err(|e| anyhow!("Send Failed: {e}"))?)
     }
 }
+
+impl Manager<Bms> {
+    /// Reads the battery voltage and updates the low battery state from it
+    fn health_metrics(&mut self) -> Result<String> {
+        let config = Config::current();
+        let bms_config = config
+            .bms
+            .as_ref()
+            .ok_or(Error::msg("[bms] is not configured"))?;
+        let voltage = self.resource.battery_voltage(bms_config)?;
+
+        let was_low = status::low_battery();
+        let low = battery_is_low(voltage, was_low, bms_config);
+        if low != was_low {
+            warn!(
+                "Battery voltage is {:.2}V, {} low battery",
+                voltage,
+                if low { "entering" } else { "leaving" }
+            );
+            if low {
+                journal::record(Event::BatteryAlarm {
+                    voltage,
+                    low_voltage: bms_config.low_voltage,
+                });
+            }
+            status::set_low_battery(low);
+        }
+        Ok(json!({ "battery_voltage": voltage, "low_battery": low }).to_string())
+    }
+}
+
+/// Battery is low below `low_voltage` and stays low until it recovers to `recovery_voltage`, so a
+/// voltage hovering around the threshold doesn't flap between states
+fn battery_is_low(voltage: f32, was_low: bool, bms_config: &BmsConfig) -> bool {
+    if was_low {
+        voltage < bms_config.recovery_voltage()
+    } else {
+        voltage < bms_config.low_voltage
+    }
+}
+
+/// Reads the battery every `bms.poll_interval_ms` so low battery is raised without a client
+/// polling `GET_HEALTH_METRICS`
+pub async fn monitor_battery(manager_channel_map: ManagerChannelMap) {
+    let Some(queue) = manager_channel_map.get(sgcp::Resource::Bms.as_str_name()) else {
+        error!("BMS resource manager not initialized, not monitoring the battery");
+        return;
+    };
+    loop {
+        let Some(poll_interval_ms) = Config::current().bms.as_ref().map(|b| b.poll_interval_ms)
+        else {
+            return;
+        };
+        let (resp_tx, resp_rx) = oneshot::channel::<String>();
+        let sent = queue
+            .send(ManagerChannelData {
+                task_code: Task::GetHealthMetrics.as_str_name().to_string(),
+                task_data: None,
+                priority: Priority::Normal,
+                source: None,
+                resp_tx,
+            })
+            .await;
+        match sent {
+            Ok(_) => match resp_rx.await {
+                Ok(res) => debug!("Polled the BMS; response={:?}", res),
+                Err(_) => warn!("BMS resource manager did not respond to a battery poll"),
+            },
+            Err(err) => warn!("Failed to poll the BMS; error={:?}", err),
+        }
+        sleep(Duration::from_millis(poll_interval_ms)).await;
+    }
+}
+
+/// Registers the BMS with the resource registry
+pub fn registration() -> Registration {
+    Registration {
+        resource: sgcp::Resource::Bms,
+        start: |queue| {
+            tokio::spawn(async move {
+                let mut manager = Manager::<Bms>::new(queue);
+                manager.run().await;
+            })
+        },
+        decode: |request| {
+            registry::decode_task(request, Task::from_str_name, |data| {
+                matches!(data, BmsData(_))
+            })
+        },
+        routine_tasks: &["GET_HEALTH_METRICS"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_battery_clears_only_once_the_battery_recovers() {
+        let bms_config: BmsConfig = toml::from_str(
+            r#"
+            cs_pin = 5
+            channel = 0
+            low_voltage = 10.5
+            recovery_voltage = 11.0
+            "#,
+        )
+        .unwrap();
+        assert!(!battery_is_low(10.6, false, &bms_config));
+        assert!(battery_is_low(10.4, false, &bms_config));
+        assert!(battery_is_low(10.8, true, &bms_config));
+        assert!(!battery_is_low(11.0, true, &bms_config));
+    }
+}
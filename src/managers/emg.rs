@@ -2,3 +2,31 @@
 mod actual;
 #[cfg(not(feature = "pi"))]
 mod mock;
+
+use crate::managers::Manager;
+use crate::managers::ResourceManager;
+use crate::managers::registry;
+use crate::managers::registry::Registration;
+use crate::request::TaskData::EmgData;
+use crate::resources::emg::Emg;
+use crate::sgcp;
+use crate::sgcp::emg::Task;
+
+/// Registers the EMG with the resource registry
+pub fn registration() -> Registration {
+    Registration {
+        resource: sgcp::Resource::Emg,
+        start: |queue| {
+            tokio::spawn(async move {
+                let mut manager = Manager::<Emg>::new(queue);
+                manager.run().await;
+            })
+        },
+        decode: |request| {
+            registry::decode_task(request, Task::from_str_name, |data| {
+                matches!(data, EmgData(_))
+            })
+        },
+        routine_tasks: &["IDLE"],
+    }
+}
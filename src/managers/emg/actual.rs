@@ -1,3 +1,4 @@
+use crate::config::Config;
 use crate::managers::Manager;
 use crate::managers::ManagerChannelData;
 use crate::managers::ResourceManager;
@@ -6,14 +7,18 @@ use crate::managers::macros::parse_channel_data;
 use crate::request::TaskData::EmgData;
 use crate::resources::emg::Emg;
 use crate::sgcp::emg::*;
+use crate::status;
+use crate::status::ElectrodeCalibration;
+use crate::status::FaultCause;
+use crate::status::Grip;
+use crate::status::SystemState;
+use crate::telemetry::stream;
+use crate::telemetry::stream::ElectrodeEnvelope;
 use anyhow::Error;
 use anyhow::Result;
 use anyhow::anyhow;
 use log::*;
 
-use rppal::gpio::{Gpio, OutputPin};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
-
 impl ResourceManager for Manager<Emg> {
     type ResourceType = Emg;
 
@@ -27,27 +32,75 @@ impl ResourceManager for Manager<Emg> {
                 Err(Error::msg("Encountered an undefined task type"))
             },
             Task::Idle => {
-                let adc_values = self.resource.read_adc_channels(&[0, 1])?;
-                info!("EMG ADC Channel 0,1 value: {:?}", adc_values);
+                let values = self.resource.read_electrodes()?;
+                let millivolts: Vec<f32> = values
+                    .iter()
+                    .map(|&v| self.resource.to_millivolts(v))
+                    .collect();
+                info!("EMG electrode values: {:?} ({:?} mV)", values, millivolts);
 
-                let grip_state = self.resource.process_data(adc_values)?;
+                let grip_state = self.resource.process_data(&values)?;
                 info!("Grip state: {:?}", grip_state);
 
-                if grip_state == 1 {
-                    info!("Opening hand");
-                    Ok("OPEN HAND".to_string())
+                // The first electrode opens the hand, the second closes it
+                // TODO: handle the case where no single electrode is active
+                let grip = if grip_state == Some(0) {
+                    Grip::Open
                 } else {
-                    // TODO: handle the case where grip_state is -1
-                    info!("Closing hand");
-                    Ok("CLOSE HAND".to_string())
+                    Grip::Closed
+                };
+
+                self.resource.update_envelopes(&millivolts);
+                stream::publish_emg(
+                    self.resource
+                        .electrodes
+                        .iter()
+                        .map(|electrode| ElectrodeEnvelope {
+                            label: electrode.label.clone(),
+                            envelope_mv: electrode.envelope,
+                        })
+                        .collect(),
+                    grip_state.map(|i| self.resource.electrodes[i].label.clone()),
+                    grip,
+                );
+
+                match grip {
+                    Grip::Open => {
+                        info!("Opening hand");
+                        Ok("OPEN HAND".to_string())
+                    },
+                    Grip::Closed => {
+                        info!("Closing hand");
+                        Ok("CLOSE HAND".to_string())
+                    },
                 }
             },
-            Task::Calibrate => match self.resource.calibrate_emg() {
-                Ok(_) => Ok(TASK_SUCCESS.to_string()),
-                Err(e) => {
-                    error!("Calibration failed: {:?}", e);
-                    Err(Error::msg(format!("Calibration failed: {}", e)))
-                },
+            Task::Calibrate => {
+                let previous = status::set_state(SystemState::Calibrating);
+                let res = match self.resource.calibrate_emg() {
+                    Ok(_) => {
+                        status::set_calibration(
+                            self.resource
+                                .electrodes
+                                .iter()
+                                .map(|electrode| ElectrodeCalibration {
+                                    label: electrode.label.clone(),
+                                    channel: electrode.channel,
+                                    threshold: electrode.threshold,
+                                })
+                                .collect(),
+                        );
+                        status::clear_fault(FaultCause::Calibration);
+                        Ok(TASK_SUCCESS.to_string())
+                    },
+                    Err(e) => {
+                        error!("Calibration failed: {:?}", e);
+                        status::raise_fault(FaultCause::Calibration);
+                        Err(Error::msg(format!("Calibration failed: {}", e)))
+                    },
+                };
+                status::set_state(previous);
+                res
             },
             Task::Abort => {
                 info!("Aborting EMG task");
@@ -70,4 +123,15 @@ impl ResourceManager for Manager<Emg> {
             .send(response)
             .map_err(|e| anyhow!("Send Failed: {e}"))?)
     }
+
+    fn reload_config(&mut self) -> Result<()> {
+        let config = Config::current();
+        let emg_config = config
+            .dispatcher
+            .emg
+            .as_ref()
+            .ok_or_else(|| Error::msg("Expected EMG config to be defined"))?;
+        self.resource.reload(emg_config);
+        Ok(())
+    }
 }
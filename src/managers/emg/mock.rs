@@ -7,6 +7,8 @@ use crate::not_on_pi;
 use crate::request::TaskData::EmgData;
 use crate::resources::emg::Emg;
 use crate::sgcp::emg::*;
+use crate::status;
+use crate::status::SystemState;
 use anyhow::Error;
 use anyhow::Result;
 use anyhow::anyhow;
@@ -29,7 +31,9 @@ impl ResourceManager for Manager<Emg> {
                 Ok(TASK_SUCCESS.to_string())
             },
             Task::Calibrate => {
+                let previous = status::set_state(SystemState::Calibrating);
                 not_on_pi!();
+                status::set_state(previous);
                 Ok(TASK_SUCCESS.to_string())
             },
             Task::Abort => {
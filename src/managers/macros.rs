@@ -15,19 +15,4 @@ macro_rules! parse_channel_data {
     }};
 }
 
-/// Provides boilerplate to initialize a resource manager and run it in its own (green) thread
-macro_rules! init_resource_managers {
-    {$($resource:expr => $variant:expr),*} => {{
-        let mut map = HashMap::new();
-        $(
-            info!("Initialising {:?} resource manager task", $resource.as_str_name());
-            let mut manager = $variant;
-            map.insert($resource.as_str_name().to_string(), manager.tx());
-            tokio::spawn(async move { manager.run().await; });
-        )*
-        map
-    }};
-}
-
-pub(crate) use init_resource_managers;
 pub(crate) use parse_channel_data;
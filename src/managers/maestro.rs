@@ -2,3 +2,70 @@
 mod actual;
 #[cfg(not(feature = "pi"))]
 mod mock;
+
+use crate::config::CommandDispatchStrategy;
+use crate::dispatchers::arbiter;
+use crate::dispatchers::estop;
+use crate::managers::Manager;
+use crate::managers::ResourceManager;
+use crate::managers::TASK_ERROR_PREFIX;
+use crate::managers::registry;
+use crate::managers::registry::Registration;
+use crate::request::TaskData::MaestroData;
+use crate::resources::maestro::Maestro;
+use crate::sgcp;
+use crate::sgcp::maestro::Task as MaestroTask;
+
+/// Internal tasks used by the e-stop to hold servos at their current position or stop driving them
+pub const FREEZE_TASK: &str = "FREEZE";
+pub const RELEASE_TASK: &str = "RELEASE";
+
+/// Checks a motion task against the e-stop and the dispatcher arbitration right before the Maestro
+/// moves, since either may have changed while the task was queued. Returns the response to reject
+/// the task with if it may not move.
+fn motion_rejection(source: Option<CommandDispatchStrategy>) -> Option<String> {
+    if estop::engaged() {
+        return Some(estop::rejection());
+    }
+    let source = source?;
+    arbiter::check_motion(source)
+        .err()
+        .map(|err| format!("{}: {}", TASK_ERROR_PREFIX, err))
+}
+
+/// Registers the Maestro with the resource registry
+pub fn registration() -> Registration {
+    Registration {
+        resource: sgcp::Resource::Maestro,
+        start: |queue| {
+            tokio::spawn(async move {
+                let mut manager = Manager::<Maestro>::new(queue);
+                manager.run().await;
+            })
+        },
+        decode: |request| {
+            registry::decode_task(request, MaestroTask::from_str_name, |data| {
+                matches!(data, MaestroData(_))
+            })
+        },
+        routine_tasks: &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_motion_is_rejected_once_overridden() {
+        assert_eq!(motion_rejection(Some(CommandDispatchStrategy::Emg)), None);
+
+        // A GPIO command carried out while the EMG task waited in the queue
+        arbiter::record_motion(CommandDispatchStrategy::Gpio);
+
+        let rejection = motion_rejection(Some(CommandDispatchStrategy::Emg)).unwrap();
+        assert!(rejection.starts_with(TASK_ERROR_PREFIX));
+        assert_eq!(motion_rejection(Some(CommandDispatchStrategy::Gpio)), None);
+        assert_eq!(motion_rejection(None), None);
+    }
+}
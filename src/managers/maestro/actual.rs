@@ -1,28 +1,53 @@
+use super::FREEZE_TASK;
+use super::RELEASE_TASK;
+use super::motion_rejection;
+use crate::dispatchers::arbiter;
 use crate::managers::Manager;
 use crate::managers::ManagerChannelData;
 use crate::managers::ResourceManager;
 use crate::managers::TASK_SUCCESS;
 use crate::managers::macros::parse_channel_data;
 use crate::request::TaskData::MaestroData;
-use crate::resources::Resource;
 use crate::resources::maestro::Maestro;
-use crate::sgcp;
 use crate::sgcp::maestro::Task as MaestroTask;
-use crate::sgcp::maestro::*;
+use crate::status;
+use crate::status::Grip;
+use crate::telemetry::stream;
 use anyhow::Error;
 use anyhow::Result;
 use anyhow::anyhow;
 use log::*;
-use raestro::maestro::{
-    builder::Builder,
-    constants::{Baudrate, Channel, MAX_QTR_PWM, MIN_QTR_PWM},
-};
-use std::time::Duration;
+use raestro::maestro::constants::{Channel, MAX_QTR_PWM, MIN_QTR_PWM};
+
+/// Servo channels driving the fingers
+const FINGER_CHANNELS: [Channel; 3] = [Channel::Channel0, Channel::Channel1, Channel::Channel2];
 
 impl ResourceManager for Manager<Maestro> {
     type ResourceType = Maestro;
 
     async fn handle_task(&mut self, channel_data: ManagerChannelData) -> Result<()> {
+        // E-stop tasks are internal to GPM and not part of SGCP
+        if let code @ (FREEZE_TASK | RELEASE_TASK) = channel_data.task_code.as_str() {
+            let freeze = code == FREEZE_TASK;
+            let response = match self.halt(freeze) {
+                Ok(_) => TASK_SUCCESS.to_string(),
+                Err(e) => format!("Error: {e}"),
+            };
+            self.publish_positions();
+            return channel_data
+                .resp_tx
+                .send(response)
+                .map_err(|e| anyhow!("Send Failed: {e}"));
+        }
+
+        let source = channel_data.source;
+        if let Some(rejection) = motion_rejection(source) {
+            return channel_data
+                .resp_tx
+                .send(rejection)
+                .map_err(|e| anyhow!("Send Failed: {e}"));
+        }
+
         let (task, _, send_channel) =
             parse_channel_data!(channel_data, MaestroTask, MaestroData).map_err(|e: Error| e)?;
 
@@ -34,26 +59,66 @@ impl ResourceManager for Manager<Maestro> {
                 Err(Error::msg("Encountered an undefined task type"))
             },
             MaestroTask::OpenFist => {
-                controller.set_target(Channel::Channel0, MIN_QTR_PWM)?;
-                controller.set_target(Channel::Channel1, MIN_QTR_PWM)?;
-                controller.set_target(Channel::Channel2, MIN_QTR_PWM)?;
+                for channel in FINGER_CHANNELS {
+                    controller.set_target(channel, MIN_QTR_PWM)?;
+                }
+                status::set_grip(Grip::Open);
                 Ok(())
             },
             MaestroTask::CloseFist => {
-                controller.set_target(Channel::Channel0, MAX_QTR_PWM)?;
-                controller.set_target(Channel::Channel1, MAX_QTR_PWM)?;
-                controller.set_target(Channel::Channel2, MAX_QTR_PWM)?;
+                for channel in FINGER_CHANNELS {
+                    controller.set_target(channel, MAX_QTR_PWM)?;
+                }
+                status::set_grip(Grip::Closed);
                 Ok(())
             },
         };
 
         let response = match task_result {
-            Ok(_) => TASK_SUCCESS.to_string(),
+            Ok(_) => {
+                // Only a command the Maestro carried out locks out other dispatchers
+                if let Some(source) = source {
+                    arbiter::record_motion(source);
+                }
+                TASK_SUCCESS.to_string()
+            },
             Err(e) => format!("Error: {e}"),
         };
+        self.publish_positions();
 
         Ok(send_channel
             .send(response)
             .map_err(|e| anyhow!("Send Failed: {e}"))?)
     }
 }
+
+impl Manager<Maestro> {
+    /// Holds every finger servo at its current position (`freeze`), or stops sending pulses to them
+    /// so they go limp
+    fn halt(&mut self, freeze: bool) -> Result<()> {
+        let controller = &mut self.resource.controller;
+        for channel in FINGER_CHANNELS {
+            // A target of 0 tells the Maestro to stop sending pulses on the channel
+            let target = if freeze {
+                controller.get_position(channel)?
+            } else {
+                0
+            };
+            controller.set_target(channel, target)?;
+        }
+        Ok(())
+    }
+
+    /// Reports the finger servo positions to the live stream
+    fn publish_positions(&mut self) {
+        let controller = &mut self.resource.controller;
+        match FINGER_CHANNELS
+            .into_iter()
+            .map(|channel| controller.get_position(channel))
+            .collect()
+        {
+            Ok(positions) => stream::publish_servo_positions(positions),
+            Err(err) => warn!("Failed to read servo positions; error={:?}", err),
+        }
+    }
+}
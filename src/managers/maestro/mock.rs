@@ -1,3 +1,7 @@
+use super::FREEZE_TASK;
+use super::RELEASE_TASK;
+use super::motion_rejection;
+use crate::dispatchers::arbiter;
 use crate::managers::Manager;
 use crate::managers::ManagerChannelData;
 use crate::managers::ResourceManager;
@@ -7,6 +11,8 @@ use crate::not_on_pi;
 use crate::request::TaskData::MaestroData;
 use crate::resources::maestro::Maestro;
 use crate::sgcp::maestro::Task as MaestroTask;
+use crate::status;
+use crate::status::Grip;
 use anyhow::Error;
 use anyhow::Result;
 use anyhow::anyhow;
@@ -17,6 +23,23 @@ impl ResourceManager for Manager<Maestro> {
 
     /// Handles all Maestro-related tasks
     async fn handle_task(&mut self, channel_data: ManagerChannelData) -> Result<()> {
+        // E-stop tasks are internal to GPM and not part of SGCP
+        if let FREEZE_TASK | RELEASE_TASK = channel_data.task_code.as_str() {
+            not_on_pi!();
+            return channel_data
+                .resp_tx
+                .send(TASK_SUCCESS.to_string())
+                .map_err(|e| anyhow!("Send Failed: {e}"));
+        }
+
+        let source = channel_data.source;
+        if let Some(rejection) = motion_rejection(source) {
+            return channel_data
+                .resp_tx
+                .send(rejection)
+                .map_err(|e| anyhow!("Send Failed: {e}"));
+        }
+
         let (task, _, send_channel) =
             parse_channel_data!(channel_data, MaestroTask, MaestroData).map_err(|e: Error| e)?;
 
@@ -27,16 +50,24 @@ impl ResourceManager for Manager<Maestro> {
             },
             MaestroTask::OpenFist => {
                 not_on_pi!();
+                status::set_grip(Grip::Open);
                 Ok(())
             },
             MaestroTask::CloseFist => {
                 not_on_pi!();
+                status::set_grip(Grip::Closed);
                 Ok(())
             },
         };
 
         let response = match task_result {
-            Ok(_) => TASK_SUCCESS.to_string(),
+            Ok(_) => {
+                // Only a command the Maestro carried out locks out other dispatchers
+                if let Some(source) = source {
+                    arbiter::record_motion(source);
+                }
+                TASK_SUCCESS.to_string()
+            },
             Err(e) => format!("Error: {e}"),
         };
 
@@ -0,0 +1,210 @@
+// Task queue in front of each resource manager. Tasks wait in one lane per priority and the
+// manager always serves the high priority lane first, so safety tasks such as the e-stop's don't
+// wait behind routine ones. Only normal priority tasks count towards the buffer size; high
+// priority tasks are always accepted.
+use super::ManagerChannelData;
+use crate::config::BackpressurePolicy;
+use crate::dispatchers::DispatchError;
+use log::*;
+use std::collections::VecDeque;
+use std::pin::pin;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+}
+
+pub struct TaskQueue {
+    resource: &'static str,
+    buffer_size: usize,
+    policy: BackpressurePolicy,
+    lanes: Mutex<Lanes>,
+    task_added: Notify,
+    space_freed: Notify,
+}
+
+#[derive(Default)]
+struct Lanes {
+    high: VecDeque<ManagerChannelData>,
+    normal: VecDeque<ManagerChannelData>,
+}
+
+impl TaskQueue {
+    pub fn new(resource: &'static str, buffer_size: usize, policy: BackpressurePolicy) -> Self {
+        TaskQueue {
+            resource,
+            buffer_size,
+            policy,
+            lanes: Mutex::new(Lanes::default()),
+            task_added: Notify::new(),
+            space_freed: Notify::new(),
+        }
+    }
+
+    /// Queues a task, applying the backpressure policy when the normal lane is full
+    pub async fn send(&self, data: ManagerChannelData) -> Result<(), DispatchError> {
+        loop {
+            // Registered before checking for space so a task taken in between isn't missed
+            let mut space_freed = pin!(self.space_freed.notified());
+            space_freed.as_mut().enable();
+            {
+                let mut lanes = self.lanes.lock().unwrap();
+                if data.priority == Priority::High {
+                    lanes.high.push_back(data);
+                    break;
+                }
+                if lanes.normal.len() < self.buffer_size {
+                    lanes.normal.push_back(data);
+                    break;
+                }
+                match self.policy {
+                    BackpressurePolicy::Block => (),
+                    BackpressurePolicy::Reject => {
+                        return Err(DispatchError::QueueFull(self.resource));
+                    },
+                    BackpressurePolicy::DropOldest => {
+                        // Dropping the task drops its responder, its dispatcher sees no response
+                        if let Some(dropped) = lanes.normal.pop_front() {
+                            warn!(
+                                resource = self.resource, task = dropped.task_code.as_str();
+                                "{} queue is full; dropping its oldest task {:?}",
+                                self.resource, dropped.task_code
+                            );
+                        }
+                        lanes.normal.push_back(data);
+                        break;
+                    },
+                }
+            }
+            space_freed.await;
+        }
+        self.task_added.notify_one();
+        Ok(())
+    }
+
+    /// Waits for the next task, high priority tasks first
+    pub async fn recv(&self) -> ManagerChannelData {
+        loop {
+            let mut task_added = pin!(self.task_added.notified());
+            task_added.as_mut().enable();
+            let next = {
+                let mut lanes = self.lanes.lock().unwrap();
+                lanes.high.pop_front().or_else(|| lanes.normal.pop_front())
+            };
+            if let Some(data) = next {
+                if data.priority == Priority::Normal {
+                    self.space_freed.notify_one();
+                }
+                return data;
+            }
+            task_added.await;
+        }
+    }
+
+    /// Takes every normal priority task out of the queue, leaving high priority ones in place
+    pub fn drain_normal(&self) -> Vec<ManagerChannelData> {
+        let drained: Vec<_> = self.lanes.lock().unwrap().normal.drain(..).collect();
+        if !drained.is_empty() {
+            self.space_freed.notify_waiters();
+        }
+        drained
+    }
+
+    /// Tasks waiting to be served
+    pub fn len(&self) -> usize {
+        let lanes = self.lanes.lock().unwrap();
+        lanes.high.len() + lanes.normal.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+    use tokio::time::timeout;
+
+    fn task(task_code: &str, priority: Priority) -> ManagerChannelData {
+        ManagerChannelData {
+            task_code: task_code.to_string(),
+            task_data: None,
+            priority,
+            source: None,
+            resp_tx: oneshot::channel().0,
+        }
+    }
+
+    async fn drain(queue: &TaskQueue) -> Vec<String> {
+        let mut task_codes = Vec::new();
+        while queue.len() > 0 {
+            task_codes.push(queue.recv().await.task_code);
+        }
+        task_codes
+    }
+
+    #[tokio::test]
+    async fn serves_high_priority_tasks_first() {
+        let queue = TaskQueue::new("MAESTRO", 4, BackpressurePolicy::Block);
+        queue
+            .send(task("CLOSE_FIST", Priority::Normal))
+            .await
+            .unwrap();
+        queue
+            .send(task("OPEN_FIST", Priority::Normal))
+            .await
+            .unwrap();
+        queue.send(task("FREEZE", Priority::High)).await.unwrap();
+        assert_eq!(drain(&queue).await, ["FREEZE", "CLOSE_FIST", "OPEN_FIST"]);
+    }
+
+    #[tokio::test]
+    async fn applies_the_backpressure_policy() {
+        let queue = TaskQueue::new("MAESTRO", 1, BackpressurePolicy::Reject);
+        queue
+            .send(task("CLOSE_FIST", Priority::Normal))
+            .await
+            .unwrap();
+        assert!(matches!(
+            queue.send(task("OPEN_FIST", Priority::Normal)).await,
+            Err(DispatchError::QueueFull(_))
+        ));
+        // High priority tasks are let in regardless
+        queue.send(task("FREEZE", Priority::High)).await.unwrap();
+        assert_eq!(drain(&queue).await, ["FREEZE", "CLOSE_FIST"]);
+
+        let queue = TaskQueue::new("MAESTRO", 1, BackpressurePolicy::DropOldest);
+        queue
+            .send(task("CLOSE_FIST", Priority::Normal))
+            .await
+            .unwrap();
+        queue
+            .send(task("OPEN_FIST", Priority::Normal))
+            .await
+            .unwrap();
+        assert_eq!(drain(&queue).await, ["OPEN_FIST"]);
+
+        let queue = Arc::new(TaskQueue::new("MAESTRO", 1, BackpressurePolicy::Block));
+        queue
+            .send(task("CLOSE_FIST", Priority::Normal))
+            .await
+            .unwrap();
+        let blocked = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.send(task("OPEN_FIST", Priority::Normal)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+        assert_eq!(queue.recv().await.task_code, "CLOSE_FIST");
+        timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(drain(&queue).await, ["OPEN_FIST"]);
+    }
+}
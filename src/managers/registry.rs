@@ -0,0 +1,176 @@
+// Registry of the resources GPM manages. Each resource's module describes how to start its
+// manager and which tasks it takes in a `Registration`; everything else (starting and supervising
+// the managers, routing tasks, validating the config) works off the registry, so adding a
+// peripheral only takes its SGCP definitions and a module registered in `builtin`.
+use super::bms;
+use super::emg;
+use super::maestro;
+use super::manager_queue;
+use super::queue::TaskQueue;
+use super::supervisor;
+use crate::ManagerChannelMap;
+use crate::config::Config;
+use crate::dispatchers::DispatchError;
+use crate::request::TaskData;
+use crate::sgcp;
+use log::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use tokio::task::JoinHandle;
+
+static REGISTRY: LazyLock<ResourceRegistry> = LazyLock::new(builtin);
+
+/// Everything GPM needs to know about a resource
+pub struct Registration {
+    pub resource: sgcp::Resource,
+    /// Spawns a manager serving the resource's tasks from `queue`. Called again whenever the
+    /// supervisor restarts the manager.
+    pub start: fn(queue: Arc<TaskQueue>) -> JoinHandle<()>,
+    /// Checks that a request's task code and data belong to the resource before it is queued
+    pub decode: fn(request: &sgcp::Request) -> Result<(), DispatchError>,
+    /// Tasks polled routinely that change nothing, only journaled when they fail
+    pub routine_tasks: &'static [&'static str],
+}
+
+impl Registration {
+    /// The resource's SGCP name, which also keys its queue in the `ManagerChannelMap`
+    pub fn key(&self) -> &'static str {
+        self.resource.as_str_name()
+    }
+}
+
+#[derive(Default)]
+pub struct ResourceRegistry {
+    registrations: Vec<Registration>,
+}
+
+impl ResourceRegistry {
+    /// The registry of every resource built into GPM
+    pub fn global() -> &'static ResourceRegistry {
+        &REGISTRY
+    }
+
+    /// Adds a resource, replacing any earlier registration for it
+    pub fn register(&mut self, registration: Registration) {
+        self.registrations
+            .retain(|existing| existing.resource != registration.resource);
+        self.registrations.push(registration);
+    }
+
+    pub fn get(&self, resource: sgcp::Resource) -> Option<&Registration> {
+        self.registrations
+            .iter()
+            .find(|registration| registration.resource == resource)
+    }
+
+    /// Whether the request is one of its resource's routine tasks
+    pub fn is_routine(&self, request: &sgcp::Request) -> bool {
+        self.get(request.resource()).is_some_and(|registration| {
+            registration
+                .routine_tasks
+                .contains(&request.task_code.as_str())
+        })
+    }
+
+    /// Finds a resource by its SGCP name, e.g. `MAESTRO`
+    pub fn lookup(&self, key: &str) -> Option<&Registration> {
+        self.registrations
+            .iter()
+            .find(|registration| registration.key() == key)
+    }
+
+    /// Starts every registered resource manager under a supervisor, returning the queues to
+    /// dispatch their tasks to
+    pub fn start_managers(&self) -> ManagerChannelMap {
+        let mut map = HashMap::new();
+        for registration in &self.registrations {
+            let name = registration.key();
+            info!("Initialising {:?} resource manager task", name);
+            let queue = manager_queue(name);
+            map.insert(name.to_string(), queue.clone());
+            let start = registration.start;
+            tokio::spawn(supervisor::supervise(
+                name,
+                &Config::global().supervisor,
+                move || start(queue.clone()),
+            ));
+        }
+        map
+    }
+}
+
+fn builtin() -> ResourceRegistry {
+    let mut registry = ResourceRegistry::default();
+    registry.register(bms::registration());
+    registry.register(emg::registration());
+    registry.register(maestro::registration());
+    registry
+}
+
+/// Decoder for resources whose task codes are the names of their SGCP `Task` enum, parsed by
+/// `from_str_name`. `is_own_data` tells the resource's variant of the task data apart.
+pub fn decode_task<T>(
+    request: &sgcp::Request,
+    from_str_name: fn(&str) -> Option<T>,
+    is_own_data: fn(&TaskData) -> bool,
+) -> Result<(), DispatchError> {
+    let resource = request.resource().as_str_name();
+    if from_str_name(&request.task_code).is_none() {
+        return Err(DispatchError::UnknownTask {
+            resource,
+            task_code: request.task_code.clone(),
+        });
+    }
+    match &request.task_data {
+        Some(data) if !is_own_data(data) => Err(DispatchError::MismatchedTaskData(resource)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(
+        resource: sgcp::Resource,
+        task_code: &str,
+        task_data: Option<TaskData>,
+    ) -> sgcp::Request {
+        sgcp::Request {
+            resource: resource as i32,
+            task_code: task_code.to_string(),
+            task_data,
+        }
+    }
+
+    #[test]
+    fn decodes_tasks_of_registered_resources() {
+        let registry = ResourceRegistry::global();
+        assert!(registry.lookup("UNDEFINED_RESOURCE").is_none());
+        let maestro = registry.lookup("MAESTRO").unwrap();
+        assert_eq!(maestro.resource, sgcp::Resource::Maestro);
+
+        let open_fist = request(sgcp::Resource::Maestro, "OPEN_FIST", None);
+        assert!((maestro.decode)(&open_fist).is_ok());
+        let calibrate = request(sgcp::Resource::Maestro, "CALIBRATE", None);
+        assert!(matches!(
+            (maestro.decode)(&calibrate),
+            Err(DispatchError::UnknownTask { .. })
+        ));
+        let bms_data = Some(TaskData::BmsData(Default::default()));
+        let open_fist = request(sgcp::Resource::Maestro, "OPEN_FIST", bms_data);
+        assert!(matches!(
+            (maestro.decode)(&open_fist),
+            Err(DispatchError::MismatchedTaskData(_))
+        ));
+    }
+
+    #[test]
+    fn only_routine_polls_are_routine() {
+        let registry = ResourceRegistry::global();
+        assert!(registry.is_routine(&request(sgcp::Resource::Emg, "IDLE", None)));
+        assert!(!registry.is_routine(&request(sgcp::Resource::Emg, "CALIBRATE", None)));
+        assert!(!registry.is_routine(&request(sgcp::Resource::Maestro, "IDLE", None)));
+    }
+}
@@ -0,0 +1,107 @@
+// Keeps every resource manager running. A manager that exits or panics is re-created, which
+// re-runs `Resource::init`, after an exponential backoff so a manager failing on startup doesn't
+// spin. Its channel survives the restart, so dispatchers never see it close; tasks in flight when
+// it died fail with `DispatchError::NoResponse`.
+use crate::config::SupervisorConfig;
+use crate::journal;
+use crate::journal::Event;
+use crate::telemetry::tasks::TASK_METRICS;
+use log::*;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+/// Resources whose manager is currently running, as opposed to waiting to be restarted
+static RUNNING: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(Default::default);
+
+pub fn is_running(resource: &str) -> bool {
+    RUNNING.lock().unwrap().contains(resource)
+}
+
+/// Runs the manager spawned by `start` until the process exits, restarting it whenever it stops
+pub async fn supervise(
+    resource: &'static str,
+    config: &'static SupervisorConfig,
+    start: impl Fn() -> JoinHandle<()>,
+) {
+    let initial_backoff = Duration::from_millis(config.initial_backoff_ms);
+    let max_backoff = Duration::from_millis(config.max_backoff_ms);
+
+    let mut backoff = initial_backoff;
+    loop {
+        let started_at = Instant::now();
+        RUNNING.lock().unwrap().insert(resource);
+        let result = start().await;
+        RUNNING.lock().unwrap().remove(resource);
+
+        let reason = match result {
+            Ok(_) => "exited".to_string(),
+            Err(err) if err.is_panic() => {
+                let payload = err.into_panic();
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                format!("panicked: {}", message)
+            },
+            Err(err) => format!("was cancelled: {}", err),
+        };
+
+        if started_at.elapsed() >= max_backoff {
+            backoff = initial_backoff;
+        }
+        error!(
+            resource = resource;
+            "{:?} resource manager {}; restarting in {:?}",
+            resource, reason, backoff
+        );
+        TASK_METRICS.record_restart(resource);
+        journal::record(Event::ManagerRestarted {
+            resource: resource.to_string(),
+            reason,
+        });
+
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(max_backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_a_panicking_manager() {
+        let config = Box::leak(Box::new(SupervisorConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        }));
+        let starts = Arc::new(AtomicUsize::new(0));
+        let counter = starts.clone();
+        tokio::spawn(supervise("TEST", config, move || {
+            let starts = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::spawn(async move {
+                if starts < 3 {
+                    panic!("manager {} failed", starts);
+                }
+                std::future::pending::<()>().await;
+            })
+        }));
+
+        // Restarted after 100ms and then 200ms
+        sleep(Duration::from_millis(250)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        assert!(!is_running("TEST"));
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        assert!(is_running("TEST"));
+    }
+}
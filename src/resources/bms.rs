@@ -1,16 +1,9 @@
-use crate::resources::Resource;
-use crate::sgcp;
+#[cfg(feature = "pi")]
+#[path = "bms/actual.rs"]
+mod bms_impl;
 
-pub struct Bms {
-    // TODO: @krarpit Implement BMS interface
-}
+#[cfg(not(feature = "pi"))]
+#[path = "bms/mock.rs"]
+mod bms_impl;
 
-impl Resource for Bms {
-    fn init() -> Self {
-        Bms {} // stub
-    }
-
-    fn name() -> String {
-        sgcp::Resource::Bms.as_str_name().to_string()
-    }
-}
+pub use bms_impl::Bms;
@@ -0,0 +1,43 @@
+// Reads the battery voltage through an ADC on the Pi
+use crate::config::BmsConfig;
+use crate::config::Config;
+use crate::resources::Resource;
+use crate::resources::common::Adc;
+use crate::resources::common::adc;
+use crate::sgcp;
+use anyhow::Error;
+use anyhow::Result;
+
+pub struct Bms {
+    // TODO: @krarpit Implement BMS interface
+    /// ADC the battery voltage is read through, `None` without a `[bms]` section
+    pub adc: Option<Box<dyn Adc + Send>>,
+}
+
+impl Resource for Bms {
+    fn init() -> Self {
+        // The ADC can't change without a restart
+        let adc = Config::global()
+            .bms
+            .as_ref()
+            .map(|bms| adc::init(&bms.adc, bms.cs_pin));
+
+        Bms { adc }
+    }
+
+    fn name() -> String {
+        sgcp::Resource::Bms.as_str_name().to_string()
+    }
+}
+
+impl Bms {
+    /// Reads the battery voltage in volts
+    pub fn battery_voltage(&mut self, bms_config: &BmsConfig) -> Result<f32> {
+        let adc = self
+            .adc
+            .as_mut()
+            .ok_or(Error::msg("No ADC to read the battery voltage from"))?;
+        let raw = adc.read_channel(bms_config.channel)?;
+        Ok(adc.to_millivolts(raw) / 1000.0 * bms_config.divider_ratio)
+    }
+}
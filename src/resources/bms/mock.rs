@@ -0,0 +1,26 @@
+// Stands in for the BMS off the Pi, where there is no ADC to read the battery from
+use crate::config::BmsConfig;
+use crate::resources::Resource;
+use crate::sgcp;
+use anyhow::Error;
+use anyhow::Result;
+
+pub struct Bms;
+
+impl Resource for Bms {
+    fn init() -> Self {
+        Bms {}
+    }
+
+    fn name() -> String {
+        sgcp::Resource::Bms.as_str_name().to_string()
+    }
+}
+
+impl Bms {
+    pub fn battery_voltage(&mut self, _bms_config: &BmsConfig) -> Result<f32> {
+        Err(Error::msg(
+            "The battery voltage can only be read on the Raspberry Pi",
+        ))
+    }
+}
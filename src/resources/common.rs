@@ -1,5 +1,6 @@
-#[cfg(feature = "pi")]
+#[cfg(any(feature = "pi", test))]
 pub mod adc;
+pub mod gpio;
 
 #[cfg(feature = "pi")]
 pub use adc::Adc;
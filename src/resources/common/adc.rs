@@ -1,76 +1,60 @@
-// MCP3008 Client
+// Generic interface over the ADCs used to sample analog sensors, along with the bus helpers shared
+// by the individual drivers. The SPI drivers are written against the `transport` traits so their
+// protocol code is tested off the Pi, only the rppal backed constructors require it.
+#[cfg(feature = "pi")]
+mod ads1115;
+#[cfg(test)]
+pub mod fake;
+mod mcp3008;
+mod mcp3208;
+pub mod transport;
+
 use anyhow::{Context, Error, Result};
 
+#[cfg(feature = "pi")]
+use crate::config::AdcConfig;
+#[cfg(feature = "pi")]
+use crate::config::AdcKind;
+#[cfg(feature = "pi")]
+use log::info;
+#[cfg(feature = "pi")]
 use rppal::gpio::{Gpio, OutputPin};
+#[cfg(feature = "pi")]
 use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
 
-pub struct Adc {
-    pub spi: Spi,
-    pub cs_pin: OutputPin,
-}
-
-impl Adc {
-    pub fn init(pin: u8) -> Self {
-        let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 500_000, Mode::Mode0)
-            .expect("Failed to initialize SPI");
-
-        let mut cs = Gpio::new()
-            .expect("Failed to initialize manual CS")
-            .get(pin)
-            .expect("Failed to get GPIO pin for CS")
-            .into_output();
-
-        cs.set_high();
-
-        Adc { spi, cs_pin: cs }
+#[cfg(feature = "pi")]
+pub use ads1115::Ads1115;
+#[cfg(feature = "pi")]
+pub use mcp3008::Mcp3008;
+#[cfg(feature = "pi")]
+pub use mcp3208::Mcp3208;
+
+/// Represents an analog-to-digital converter
+pub trait Adc {
+    /// Reads the raw conversion result of a single-ended channel
+    fn read_channel(&mut self, channel: u8) -> Result<u16>;
+
+    /// Reads the raw conversion result of `positive` measured against `negative`
+    fn read_differential(&mut self, positive: u8, negative: u8) -> Result<u16> {
+        Err(Error::msg(format!(
+            "Differential reads (CH{}/CH{}) are not supported by this ADC",
+            positive, negative
+        )))
     }
 
-    // Reads the 10-bit ADC value from a given channel (0–7) on the MCP3008 via SPI.
-    // MCP3008 messaging protocol: 3 byte message structure
-    // doc link: https://www.mathworks.com/help/matlab/supportpkg/analog-input-using-spi.html
-    pub fn read_channel(&mut self, channel: u8) -> Result<u16> {
-        if channel > 7 {
-            return Err(Error::msg(format!(
-                "Invalid ADC channel: {}. Must be between 0 and 7.",
-                channel
-            )));
-        }
-
-        // first byte: start sequence, sends 1 as start bit
-        let start_bit = 0b00000001;
-
-        // second byte: mode (differential or single read) and channel select
-        // sets bit 7 to 1 first, then left-shifts channel number by 4 bits to put it into bits 6,5,4
-        // then combines with bitwise OR operator
-        // remaining bits are ignored
-        let config_bits = 0b10000000 | (channel << 4);
-
-        // third byte: dummy to clock out rest of ADC result
-        let tx = [start_bit, config_bits, 0x00];
-
-        // response buffer
-        let mut rx = [0u8; 3];
-
-        //activate chip select
-        self.cs_pin.set_low();
+    /// Number of bits in a full scale reading
+    fn resolution_bits(&self) -> u8;
 
-        // full-duplex SPI transfer: sends tx[], fills rx[]
-        self.spi
-            .transfer(&mut rx, &tx)
-            .context("SPI transfer failed during ADC read")?;
+    /// Voltage corresponding to a full scale reading
+    fn reference_voltage(&self) -> f32;
 
-        // deactivate chip select
-        self.cs_pin.set_high();
-
-        // adc sends back 3 byte response
-        // actual response is 10 bits and spread across rx[1](bits 9-8) and rx[2](bits 7-0)
-        // isolate rx[1] result bits, then shift them to 9-8 in a 16 bit number, then add remaining bits by combining them with bitwise OR
-        let result = ((rx[1] & 0b00000011) as u16) << 8 | (rx[2] as u16);
-
-        Ok(result)
+    /// Converts a raw reading to millivolts
+    fn to_millivolts(&self, raw: u16) -> f32 {
+        let full_scale = ((1u32 << self.resolution_bits()) - 1) as f32;
+        raw as f32 * self.reference_voltage() * 1000.0 / full_scale
     }
 
-    pub fn read_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>> {
+    fn read_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>> {
         channels
             .iter()
             .map(|&channel| {
@@ -79,14 +63,63 @@ impl Adc {
             })
             .collect()
     }
+}
+
+/// Initializes the ADC described by the given config. `cs_pin` is the GPIO pin used as a manual
+/// chip select by the SPI parts.
+#[cfg(feature = "pi")]
+pub fn init(config: &AdcConfig, cs_pin: u8) -> Box<dyn Adc + Send> {
+    info!("Initializing {:?} ADC", config.kind);
+    match config.kind {
+        AdcKind::Mcp3008 => Box::new(Mcp3008::init(config, cs_pin)),
+        AdcKind::Mcp3208 => Box::new(Mcp3208::init(config, cs_pin)),
+        AdcKind::Ads1115 => Box::new(Ads1115::init(config)),
+    }
+}
 
-    // Averages ADC readings
-    pub fn average_values(list: &Vec<u16>) -> Result<u16> {
-        if list.is_empty() {
-            return Err(Error::msg("Cannot calculate average of an empty list"));
-        } else {
-            let sum: u32 = list.iter().map(|&x| x as u32).sum();
-            Ok((sum / list.len() as u32) as u16)
-        }
+// Averages ADC readings
+#[cfg(feature = "pi")]
+pub fn average_values(list: &[u16]) -> Result<u16> {
+    if list.is_empty() {
+        Err(Error::msg("Cannot calculate average of an empty list"))
+    } else {
+        let sum: u32 = list.iter().map(|&x| x as u32).sum();
+        Ok((sum / list.len() as u32) as u16)
     }
 }
+
+/// Opens the SPI device described by the given config
+#[cfg(feature = "pi")]
+fn open_spi(config: &AdcConfig) -> Spi {
+    let bus = match config.spi_bus {
+        0 => Bus::Spi0,
+        1 => Bus::Spi1,
+        2 => Bus::Spi2,
+        3 => Bus::Spi3,
+        4 => Bus::Spi4,
+        5 => Bus::Spi5,
+        6 => Bus::Spi6,
+        bus => panic!("Invalid SPI bus: {}. Must be between 0 and 6.", bus),
+    };
+    let slave_select = match config.slave_select {
+        0 => SlaveSelect::Ss0,
+        1 => SlaveSelect::Ss1,
+        2 => SlaveSelect::Ss2,
+        ss => panic!("Invalid SPI slave select: {}. Must be between 0 and 2.", ss),
+    };
+    Spi::new(bus, slave_select, config.clock_speed_hz, Mode::Mode0)
+        .expect("Failed to initialize SPI")
+}
+
+/// Claims the given GPIO pin as a manual (active low) chip select line
+#[cfg(feature = "pi")]
+fn manual_cs(pin: u8) -> OutputPin {
+    let mut cs = Gpio::new()
+        .expect("Failed to initialize manual CS")
+        .get(pin)
+        .expect("Failed to get GPIO pin for CS")
+        .into_output();
+
+    cs.set_high();
+    cs
+}
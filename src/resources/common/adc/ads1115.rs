@@ -0,0 +1,125 @@
+// ADS1115 Client
+use super::Adc;
+use crate::config::AdcConfig;
+use anyhow::{Context, Error, Result};
+use log::info;
+use std::{thread, time::Duration};
+
+use rppal::i2c::I2c;
+
+// Register pointers
+const CONVERSION_REGISTER: u8 = 0x00;
+const CONFIG_REGISTER: u8 = 0x01;
+
+// Config register fields
+const OS_START_SINGLE: u16 = 0b1 << 15;
+const MUX_SINGLE_ENDED: u16 = 0b100 << 12;
+const MODE_SINGLE_SHOT: u16 = 0b1 << 8;
+const DATA_RATE_128_SPS: u16 = 0b100 << 5;
+const COMPARATOR_DISABLED: u16 = 0b11;
+
+/// Full scale ranges supported by the programmable gain amplifier and their PGA bits, from widest
+/// to narrowest
+const FULL_SCALE_RANGES: [(f32, u16); 6] = [
+    (6.144, 0b000),
+    (4.096, 0b001),
+    (2.048, 0b010),
+    (1.024, 0b011),
+    (0.512, 0b100),
+    (0.256, 0b101),
+];
+
+/// Number of times the config register is polled for a finished conversion before giving up
+const MAX_CONVERSION_POLLS: usize = 10;
+
+pub struct Ads1115 {
+    pub i2c: I2c,
+    /// PGA bits of the config register, already shifted into place
+    pub gain_bits: u16,
+    /// Full scale range selected by `gain_bits`
+    pub full_scale: f32,
+}
+
+impl Ads1115 {
+    pub fn init(config: &AdcConfig) -> Self {
+        let mut i2c = I2c::with_bus(config.i2c_bus).expect("Failed to initialize I2C");
+        i2c.set_slave_address(config.i2c_address)
+            .expect("Failed to set ADS1115 I2C address");
+
+        // Narrowest full scale range that still covers the reference voltage
+        let (full_scale, gain_bits) = FULL_SCALE_RANGES
+            .iter()
+            .rev()
+            .find(|(full_scale, _)| *full_scale >= config.reference_voltage)
+            .copied()
+            .unwrap_or(FULL_SCALE_RANGES[0]);
+        info!("Using ADS1115 full scale range of ±{}V", full_scale);
+
+        Ads1115 {
+            i2c,
+            gain_bits: gain_bits << 9,
+            full_scale,
+        }
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16> {
+        let mut rx = [0u8; 2];
+        self.i2c
+            .write_read(&[register], &mut rx)
+            .context("I2C transfer failed during ADC read")?;
+        Ok(u16::from_be_bytes(rx))
+    }
+}
+
+impl Adc for Ads1115 {
+    // Reads the 16-bit ADC value from a given channel (0–3) on the ADS1115 via I2C by triggering a
+    // single-shot conversion and polling until it completes.
+    // doc link: https://www.ti.com/lit/ds/symlink/ads1115.pdf
+    fn read_channel(&mut self, channel: u8) -> Result<u16> {
+        if channel > 3 {
+            return Err(Error::msg(format!(
+                "Invalid ADC channel: {}. Must be between 0 and 3.",
+                channel
+            )));
+        }
+
+        let config = OS_START_SINGLE
+            | MUX_SINGLE_ENDED
+            | (channel as u16) << 12
+            | self.gain_bits
+            | MODE_SINGLE_SHOT
+            | DATA_RATE_128_SPS
+            | COMPARATOR_DISABLED;
+        let [msb, lsb] = config.to_be_bytes();
+        self.i2c
+            .write(&[CONFIG_REGISTER, msb, lsb])
+            .context("I2C transfer failed when starting ADC conversion")?;
+
+        // A conversion takes ~8ms at 128 SPS, the OS bit reads back as 1 once it is done
+        let mut polls = 0;
+        loop {
+            thread::sleep(Duration::from_millis(2));
+            if self.read_register(CONFIG_REGISTER)? & OS_START_SINGLE != 0 {
+                break;
+            }
+            polls += 1;
+            if polls >= MAX_CONVERSION_POLLS {
+                return Err(Error::msg("Timed out waiting for ADS1115 conversion"));
+            }
+        }
+
+        // Result is a signed 16-bit value, single-ended readings can only go slightly negative
+        // due to offset error so they are clamped at 0
+        let result = self.read_register(CONVERSION_REGISTER)? as i16;
+        Ok(result.max(0) as u16)
+    }
+
+    // Readings are signed, so positive full scale is 15 bits
+    fn resolution_bits(&self) -> u8 {
+        15
+    }
+
+    fn reference_voltage(&self) -> f32 {
+        self.full_scale
+    }
+}
@@ -0,0 +1,69 @@
+// Scripted in-memory stand-ins for the SPI transport, used to test the ADC drivers
+use super::transport::ChipSelect;
+use super::transport::SpiTransport;
+use anyhow::Error;
+use anyhow::Result;
+use std::collections::VecDeque;
+
+/// Replays canned responses, one per transfer or segmented transfer sequence, and records what
+/// each of them transmitted
+#[derive(Default)]
+pub struct ScriptedSpi {
+    pub responses: VecDeque<Vec<u8>>,
+    pub sent: Vec<Vec<u8>>,
+}
+
+impl ScriptedSpi {
+    pub fn new(responses: &[&[u8]]) -> Self {
+        ScriptedSpi {
+            responses: responses.iter().map(|r| r.to_vec()).collect(),
+            sent: Vec::new(),
+        }
+    }
+}
+
+impl SpiTransport for ScriptedSpi {
+    fn transfer(&mut self, rx: &mut [u8], tx: &[u8]) -> Result<()> {
+        self.sent.push(tx.to_vec());
+        let response = self
+            .responses
+            .pop_front()
+            .ok_or(Error::msg("No scripted SPI response left"))?;
+        if response.len() != rx.len() {
+            return Err(Error::msg(format!(
+                "Scripted response has {} bytes but transfer expects {}",
+                response.len(),
+                rx.len()
+            )));
+        }
+        rx.copy_from_slice(&response);
+        Ok(())
+    }
+
+    fn transfer_segments(&mut self, rx: &mut [u8], tx: &[u8], segment_length: usize) -> Result<()> {
+        if !tx.len().is_multiple_of(segment_length) {
+            return Err(Error::msg(format!(
+                "{} bytes don't split into {} byte segments",
+                tx.len(),
+                segment_length
+            )));
+        }
+        self.transfer(rx, tx)
+    }
+}
+
+/// Records the chip select level after every change, `true` meaning selected
+#[derive(Default)]
+pub struct RecordingChipSelect {
+    pub events: Vec<bool>,
+}
+
+impl ChipSelect for RecordingChipSelect {
+    fn select(&mut self) {
+        self.events.push(true);
+    }
+
+    fn deselect(&mut self) {
+        self.events.push(false);
+    }
+}
@@ -0,0 +1,249 @@
+// MCP3008 Client
+use super::Adc;
+use super::transport::ChipSelect;
+use super::transport::SpiTransport;
+use super::transport::transfer_frames;
+use anyhow::{Error, Result};
+
+#[cfg(feature = "pi")]
+use crate::config::AdcConfig;
+#[cfg(feature = "pi")]
+use rppal::gpio::OutputPin;
+#[cfg(feature = "pi")]
+use rppal::spi::Spi;
+
+/// Number of bytes exchanged per conversion
+const FRAME_LENGTH: usize = 3;
+
+pub struct Mcp3008<S: SpiTransport, C: ChipSelect> {
+    pub spi: S,
+    pub cs_pin: C,
+    pub reference_voltage: f32,
+}
+
+#[cfg(feature = "pi")]
+impl Mcp3008<Spi, OutputPin> {
+    pub fn init(config: &AdcConfig, cs_pin: u8) -> Self {
+        Mcp3008::new(
+            super::open_spi(config),
+            super::manual_cs(cs_pin),
+            config.reference_voltage,
+        )
+    }
+}
+
+impl<S: SpiTransport, C: ChipSelect> Mcp3008<S, C> {
+    pub fn new(spi: S, cs_pin: C, reference_voltage: f32) -> Self {
+        Mcp3008 {
+            spi,
+            cs_pin,
+            reference_voltage,
+        }
+    }
+
+    fn transfer_frames(&mut self, rx: &mut [u8], tx: &[u8]) -> Result<()> {
+        transfer_frames(&mut self.spi, &mut self.cs_pin, rx, tx, FRAME_LENGTH)
+    }
+}
+
+impl<S: SpiTransport, C: ChipSelect> Adc for Mcp3008<S, C> {
+    // Reads the 10-bit ADC value from a given channel (0–7) on the MCP3008 via SPI.
+    // MCP3008 messaging protocol: 3 byte message structure
+    // doc link: https://www.mathworks.com/help/matlab/supportpkg/analog-input-using-spi.html
+    fn read_channel(&mut self, channel: u8) -> Result<u16> {
+        validate_channel(channel)?;
+
+        let tx = command(channel, true);
+
+        // response buffer
+        let mut rx = [0u8; FRAME_LENGTH];
+
+        self.transfer_frames(&mut rx, &tx)?;
+
+        Ok(parse_result(&rx))
+    }
+
+    // Differential pairs are CH0/CH1, CH2/CH3, CH4/CH5 and CH6/CH7, with either channel of a pair
+    // usable as the positive input
+    fn read_differential(&mut self, positive: u8, negative: u8) -> Result<u16> {
+        validate_channel(positive)?;
+        if negative != positive ^ 1 {
+            return Err(Error::msg(format!(
+                "Invalid differential pair: CH{}/CH{}. Channels must be CH0/CH1, CH2/CH3, CH4/CH5 or CH6/CH7.",
+                positive, negative
+            )));
+        }
+
+        // In differential mode the channel bits select the pair (D2, D1) and which channel of the
+        // pair is the positive input (D0), which works out to the positive channel number
+        let tx = command(positive, false);
+        let mut rx = [0u8; FRAME_LENGTH];
+
+        self.transfer_frames(&mut rx, &tx)?;
+
+        Ok(parse_result(&rx))
+    }
+
+    fn resolution_bits(&self) -> u8 {
+        10
+    }
+
+    fn reference_voltage(&self) -> f32 {
+        self.reference_voltage
+    }
+
+    // Batched read: every command is built up front and clocked out as one transfer sequence, the
+    // controller releasing chip select between frames so each one starts a new conversion
+    fn read_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>> {
+        let mut tx = Vec::with_capacity(channels.len() * FRAME_LENGTH);
+        for &channel in channels {
+            validate_channel(channel)?;
+            tx.extend_from_slice(&command(channel, true));
+        }
+        let mut rx = vec![0u8; tx.len()];
+
+        self.cs_pin.select();
+        let result = self.spi.transfer_segments(&mut rx, &tx, FRAME_LENGTH);
+        // deactivate chip select, even if the transfer failed
+        self.cs_pin.deselect();
+        result?;
+
+        Ok(rx.chunks_exact(FRAME_LENGTH).map(parse_result).collect())
+    }
+}
+
+fn validate_channel(channel: u8) -> Result<()> {
+    if channel > 7 {
+        return Err(Error::msg(format!(
+            "Invalid ADC channel: {}. Must be between 0 and 7.",
+            channel
+        )));
+    }
+    Ok(())
+}
+
+/// Builds the 3 byte command frame for a conversion on the given channel
+fn command(channel: u8, single_ended: bool) -> [u8; FRAME_LENGTH] {
+    // first byte: start sequence, sends 1 as start bit
+    let start_bit = 0b00000001;
+
+    // second byte: mode (differential or single read) and channel select
+    // sets bit 7 to 1 for single-ended reads (0 for differential), then left-shifts channel number
+    // by 4 bits to put it into bits 6,5,4 then combines with bitwise OR operator
+    // remaining bits are ignored
+    let mode_bit = if single_ended { 0b10000000 } else { 0 };
+    let config_bits = mode_bit | (channel << 4);
+
+    // third byte: dummy to clock out rest of ADC result
+    [start_bit, config_bits, 0x00]
+}
+
+/// Extracts the conversion result from a 3 byte response frame
+fn parse_result(rx: &[u8]) -> u16 {
+    // adc sends back 3 byte response
+    // actual response is 10 bits and spread across rx[1](bits 9-8) and rx[2](bits 7-0)
+    // isolate rx[1] result bits, then shift them to 9-8 in a 16 bit number, then add remaining bits by combining them with bitwise OR
+    ((rx[1] & 0b00000011) as u16) << 8 | (rx[2] as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::common::adc::fake::RecordingChipSelect;
+    use crate::resources::common::adc::fake::ScriptedSpi;
+
+    fn adc(responses: &[&[u8]]) -> Mcp3008<ScriptedSpi, RecordingChipSelect> {
+        Mcp3008::new(
+            ScriptedSpi::new(responses),
+            RecordingChipSelect::default(),
+            3.3,
+        )
+    }
+
+    #[test]
+    fn single_ended_read_sends_start_and_config_bits() {
+        let mut adc = adc(&[&[0x00, 0x00, 0x00]]);
+        adc.read_channel(5).unwrap();
+        assert_eq!(adc.spi.sent, vec![vec![0b0000_0001, 0b1101_0000, 0x00]]);
+        assert_eq!(adc.cs_pin.events, vec![true, false]);
+    }
+
+    #[test]
+    fn result_is_reassembled_from_the_low_bits_of_the_response() {
+        // Only the low two bits of the second byte belong to the result
+        let mut adc = adc(&[&[0xFF, 0b1111_1110, 0xAB]]);
+        assert_eq!(adc.read_channel(0).unwrap(), 0x2AB);
+    }
+
+    #[test]
+    fn invalid_channel_is_rejected_without_a_transfer() {
+        let mut adc = adc(&[]);
+        assert!(adc.read_channel(8).is_err());
+        assert!(adc.spi.sent.is_empty());
+        assert!(adc.cs_pin.events.is_empty());
+    }
+
+    #[test]
+    fn differential_read_clears_the_single_ended_bit() {
+        let mut adc = adc(&[&[0x00, 0x01, 0x00], &[0x00, 0x00, 0x10]]);
+        assert_eq!(adc.read_differential(2, 3).unwrap(), 0x100);
+        assert_eq!(adc.read_differential(3, 2).unwrap(), 0x010);
+        assert_eq!(
+            adc.spi.sent,
+            vec![
+                vec![0b0000_0001, 0b0010_0000, 0x00],
+                vec![0b0000_0001, 0b0011_0000, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn differential_read_rejects_channels_from_different_pairs() {
+        let mut adc = adc(&[]);
+        assert!(adc.read_differential(1, 2).is_err());
+        assert!(adc.spi.sent.is_empty());
+    }
+
+    #[test]
+    fn several_channels_are_read_in_one_transfer_sequence() {
+        let mut adc = adc(&[&[0x00, 0x03, 0xFF, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00]]);
+        assert_eq!(adc.read_channels(&[0, 7, 3]).unwrap(), vec![1023, 1, 512]);
+        assert_eq!(
+            adc.spi.sent,
+            vec![vec![
+                0b0000_0001,
+                0b1000_0000,
+                0x00,
+                0b0000_0001,
+                0b1111_0000,
+                0x00,
+                0b0000_0001,
+                0b1011_0000,
+                0x00,
+            ]]
+        );
+        assert_eq!(adc.cs_pin.events, vec![true, false]);
+    }
+
+    #[test]
+    fn batched_read_rejects_an_invalid_channel_without_a_transfer() {
+        let mut adc = adc(&[]);
+        assert!(adc.read_channels(&[0, 8]).is_err());
+        assert!(adc.spi.sent.is_empty());
+        assert!(adc.cs_pin.events.is_empty());
+    }
+
+    #[test]
+    fn chip_select_is_released_when_a_transfer_fails() {
+        let mut adc = adc(&[]);
+        assert!(adc.read_channel(0).is_err());
+        assert_eq!(adc.cs_pin.events, vec![true, false]);
+    }
+
+    #[test]
+    fn full_scale_reading_converts_to_the_reference_voltage() {
+        let adc = adc(&[]);
+        assert_eq!(adc.to_millivolts(1023), 3300.0);
+        assert_eq!(adc.to_millivolts(0), 0.0);
+    }
+}
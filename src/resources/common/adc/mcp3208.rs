@@ -0,0 +1,130 @@
+// MCP3208 Client
+use super::Adc;
+use super::transport::ChipSelect;
+use super::transport::SpiTransport;
+use super::transport::transfer_frames;
+use anyhow::{Error, Result};
+
+#[cfg(feature = "pi")]
+use crate::config::AdcConfig;
+#[cfg(feature = "pi")]
+use rppal::gpio::OutputPin;
+#[cfg(feature = "pi")]
+use rppal::spi::Spi;
+
+pub struct Mcp3208<S: SpiTransport, C: ChipSelect> {
+    pub spi: S,
+    pub cs_pin: C,
+    pub reference_voltage: f32,
+}
+
+#[cfg(feature = "pi")]
+impl Mcp3208<Spi, OutputPin> {
+    pub fn init(config: &AdcConfig, cs_pin: u8) -> Self {
+        Mcp3208::new(
+            super::open_spi(config),
+            super::manual_cs(cs_pin),
+            config.reference_voltage,
+        )
+    }
+}
+
+impl<S: SpiTransport, C: ChipSelect> Mcp3208<S, C> {
+    pub fn new(spi: S, cs_pin: C, reference_voltage: f32) -> Self {
+        Mcp3208 {
+            spi,
+            cs_pin,
+            reference_voltage,
+        }
+    }
+}
+
+impl<S: SpiTransport, C: ChipSelect> Adc for Mcp3208<S, C> {
+    // Reads the 12-bit ADC value from a given channel (0–7) on the MCP3208 via SPI.
+    // Same 3 byte exchange as the MCP3008, but the start bit is moved two bits to the left so that
+    // the two extra result bits fit in the response
+    fn read_channel(&mut self, channel: u8) -> Result<u16> {
+        if channel > 7 {
+            return Err(Error::msg(format!(
+                "Invalid ADC channel: {}. Must be between 0 and 7.",
+                channel
+            )));
+        }
+
+        // first byte: start bit (bit 2), single-ended mode (bit 1) and channel bit D2 (bit 0)
+        let start_bits = 0b00000110 | (channel >> 2);
+
+        // second byte: channel bits D1 and D0 in bits 7 and 6, remaining bits are ignored
+        let config_bits = (channel & 0b00000011) << 6;
+
+        // third byte: dummy to clock out rest of ADC result
+        let tx = [start_bits, config_bits, 0x00];
+        let mut rx = [0u8; 3];
+
+        transfer_frames(&mut self.spi, &mut self.cs_pin, &mut rx, &tx, tx.len())?;
+
+        // result is 12 bits spread across rx[1] (bits 11-8) and rx[2] (bits 7-0)
+        let result = ((rx[1] & 0b00001111) as u16) << 8 | (rx[2] as u16);
+
+        Ok(result)
+    }
+
+    fn resolution_bits(&self) -> u8 {
+        12
+    }
+
+    fn reference_voltage(&self) -> f32 {
+        self.reference_voltage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::common::adc::fake::RecordingChipSelect;
+    use crate::resources::common::adc::fake::ScriptedSpi;
+
+    fn adc(responses: &[&[u8]]) -> Mcp3208<ScriptedSpi, RecordingChipSelect> {
+        Mcp3208::new(
+            ScriptedSpi::new(responses),
+            RecordingChipSelect::default(),
+            3.3,
+        )
+    }
+
+    #[test]
+    fn read_splits_the_channel_across_the_first_two_bytes() {
+        let mut adc = adc(&[&[0x00, 0x00, 0x00], &[0x00, 0x00, 0x00]]);
+        adc.read_channel(5).unwrap();
+        adc.read_channel(2).unwrap();
+        assert_eq!(
+            adc.spi.sent,
+            vec![
+                vec![0b0000_0111, 0b0100_0000, 0x00],
+                vec![0b0000_0110, 0b1000_0000, 0x00],
+            ]
+        );
+        assert_eq!(adc.cs_pin.events, vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn result_is_reassembled_from_the_low_nibble_of_the_response() {
+        // Only the low four bits of the second byte belong to the result
+        let mut adc = adc(&[&[0xFF, 0b1111_1010, 0xCD]]);
+        assert_eq!(adc.read_channel(0).unwrap(), 0xACD);
+    }
+
+    #[test]
+    fn invalid_channel_is_rejected_without_a_transfer() {
+        let mut adc = adc(&[]);
+        assert!(adc.read_channel(8).is_err());
+        assert!(adc.spi.sent.is_empty());
+        assert!(adc.cs_pin.events.is_empty());
+    }
+
+    #[test]
+    fn full_scale_reading_converts_to_the_reference_voltage() {
+        let adc = adc(&[]);
+        assert_eq!(adc.to_millivolts(4095), 3300.0);
+    }
+}
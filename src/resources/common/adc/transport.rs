@@ -0,0 +1,88 @@
+// Bus abstractions the SPI ADC drivers are written against, so that the protocol code does not
+// depend on rppal and can be exercised off the Pi
+use anyhow::Result;
+
+#[cfg(feature = "pi")]
+use anyhow::Context;
+#[cfg(feature = "pi")]
+use rppal::gpio::OutputPin;
+#[cfg(feature = "pi")]
+use rppal::spi::Segment;
+#[cfg(feature = "pi")]
+use rppal::spi::Spi;
+
+/// A full-duplex SPI link
+pub trait SpiTransport {
+    /// Clocks out `tx` while filling `rx` with the bytes clocked in
+    fn transfer(&mut self, rx: &mut [u8], tx: &[u8]) -> Result<()>;
+
+    /// Clocks out `tx` as a single transfer sequence of `segment_length` byte segments, with the
+    /// controller's chip select released between segments, filling the matching bytes of `rx`
+    fn transfer_segments(&mut self, rx: &mut [u8], tx: &[u8], segment_length: usize) -> Result<()>;
+}
+
+/// An active low chip select line
+pub trait ChipSelect {
+    fn select(&mut self);
+    fn deselect(&mut self);
+}
+
+#[cfg(feature = "pi")]
+impl SpiTransport for Spi {
+    fn transfer(&mut self, rx: &mut [u8], tx: &[u8]) -> Result<()> {
+        Spi::transfer(self, rx, tx)
+            .map(|_| ())
+            .context("SPI transfer failed during ADC read")
+    }
+
+    fn transfer_segments(&mut self, rx: &mut [u8], tx: &[u8], segment_length: usize) -> Result<()> {
+        let mut segments: Vec<Segment> = rx
+            .chunks_exact_mut(segment_length)
+            .zip(tx.chunks_exact(segment_length))
+            .map(|(rx_segment, tx_segment)| {
+                let mut segment = Segment::new(rx_segment, tx_segment);
+                segment.set_cs_change(true);
+                segment
+            })
+            .collect();
+        // Setting it on the last segment would leave chip select active after the sequence
+        if let Some(last) = segments.last_mut() {
+            last.set_cs_change(false);
+        }
+        Spi::transfer_segments(self, &segments).context("SPI transfer failed during ADC read")
+    }
+}
+
+#[cfg(feature = "pi")]
+impl ChipSelect for OutputPin {
+    fn select(&mut self) {
+        self.set_low();
+    }
+
+    fn deselect(&mut self) {
+        self.set_high();
+    }
+}
+
+/// Runs one SPI transfer per `frame_length` bytes of `tx`, filling the matching bytes of `rx`.
+/// Chip select is released between frames since the MCP3x08 parts only start a new conversion on
+/// its falling edge.
+pub fn transfer_frames(
+    spi: &mut impl SpiTransport,
+    cs: &mut impl ChipSelect,
+    rx: &mut [u8],
+    tx: &[u8],
+    frame_length: usize,
+) -> Result<()> {
+    for (rx_frame, tx_frame) in rx
+        .chunks_exact_mut(frame_length)
+        .zip(tx.chunks_exact(frame_length))
+    {
+        cs.select();
+        let result = spi.transfer(rx_frame, tx_frame);
+        // deactivate chip select, even if the transfer failed
+        cs.deselect();
+        result?;
+    }
+    Ok(())
+}
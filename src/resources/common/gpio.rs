@@ -0,0 +1,38 @@
+// Minimal GPIO interface shared by everything that reads buttons or drives indicator pins. On the
+// Pi it is backed by rppal, elsewhere by a simulated set of pins that can be driven from tests or
+// over a Unix socket.
+#[cfg(feature = "pi")]
+#[path = "gpio/actual.rs"]
+mod gpio_impl;
+
+#[cfg(not(feature = "pi"))]
+#[path = "gpio/mock.rs"]
+mod gpio_impl;
+
+use anyhow::Result;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+
+pub use gpio_impl::Gpio;
+
+/// A level change on an input pin
+#[derive(Debug, Clone, Copy)]
+pub struct Edge {
+    pub high: bool,
+    pub at: Instant,
+}
+
+pub trait GpioInterface {
+    /// Configures `pin` as a pulled up input and forwards every level change on it to `edges`
+    /// for as long as this interface is alive
+    fn watch(&mut self, pin: u8, edges: UnboundedSender<Edge>) -> Result<()>;
+
+    /// Claims `pin` as an output, initially driven low
+    fn output(&mut self, pin: u8) -> Result<Box<dyn OutputLine + Send>>;
+}
+
+/// A pin driven by GPM
+pub trait OutputLine {
+    fn set_high(&mut self);
+    fn set_low(&mut self);
+}
@@ -0,0 +1,68 @@
+// GPIO backed by rppal
+use super::Edge;
+use super::GpioInterface;
+use super::OutputLine;
+use anyhow::Context;
+use anyhow::Result;
+use rppal::gpio::InputPin;
+use rppal::gpio::OutputPin;
+use rppal::gpio::Trigger;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+
+pub struct Gpio {
+    gpio: rppal::gpio::Gpio,
+    /// Watched pins, dropping them would disable their interrupts
+    inputs: Vec<InputPin>,
+}
+
+impl Gpio {
+    pub fn new() -> Result<Self> {
+        Ok(Gpio {
+            gpio: rppal::gpio::Gpio::new().context("Failed to initialize GPIO")?,
+            inputs: Vec::new(),
+        })
+    }
+}
+
+impl GpioInterface for Gpio {
+    fn watch(&mut self, pin: u8, edges: UnboundedSender<Edge>) -> Result<()> {
+        let mut input = self
+            .gpio
+            .get(pin)
+            .with_context(|| format!("Failed to access pin {}", pin))?
+            .into_input_pullup();
+
+        // Runs on rppal's interrupt thread
+        input
+            .set_async_interrupt(Trigger::Both, None, move |event| {
+                let _ = edges.send(Edge {
+                    high: event.trigger == Trigger::RisingEdge,
+                    at: Instant::now(),
+                });
+            })
+            .with_context(|| format!("Failed to set up interrupt on pin {}", pin))?;
+
+        self.inputs.push(input);
+        Ok(())
+    }
+
+    fn output(&mut self, pin: u8) -> Result<Box<dyn OutputLine + Send>> {
+        let output = self
+            .gpio
+            .get(pin)
+            .with_context(|| format!("Failed to access pin {}", pin))?
+            .into_output_low();
+        Ok(Box::new(output))
+    }
+}
+
+impl OutputLine for OutputPin {
+    fn set_high(&mut self) {
+        OutputPin::set_high(self);
+    }
+
+    fn set_low(&mut self) {
+        OutputPin::set_low(self);
+    }
+}
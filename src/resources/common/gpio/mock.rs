@@ -0,0 +1,135 @@
+// Simulated GPIO used outside the Pi. Input levels are set with `set_input` (from tests, or by
+// writing "<pin> <high|low>" lines to the socket started with `serve`) and output levels can be
+// read back with `output_level`.
+use super::Edge;
+use super::GpioInterface;
+use super::OutputLine;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+use log::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::BufReader;
+use tokio::net::UnixListener;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+
+#[derive(Default)]
+struct Pins {
+    /// Input levels, pins read high (pulled up) until set otherwise
+    inputs: HashMap<u8, bool>,
+    watchers: HashMap<u8, Vec<UnboundedSender<Edge>>>,
+    outputs: HashMap<u8, bool>,
+}
+
+/// Pins shared by every `Gpio::new()` handle, so the socket and the dispatchers see the same state
+static SIMULATED_PINS: LazyLock<Arc<Mutex<Pins>>> = LazyLock::new(Arc::default);
+
+/// Handle to a set of simulated pins
+#[derive(Clone, Default)]
+pub struct Gpio {
+    pins: Arc<Mutex<Pins>>,
+}
+
+impl Gpio {
+    pub fn new() -> Result<Self> {
+        Ok(Gpio {
+            pins: SIMULATED_PINS.clone(),
+        })
+    }
+
+    /// Drives a simulated input pin, notifying its watchers if the level changed
+    pub fn set_input(&self, pin: u8, high: bool) {
+        let mut pins = self.pins.lock().unwrap();
+        if pins.inputs.insert(pin, high).unwrap_or(true) == high {
+            return;
+        }
+        let edge = Edge {
+            high,
+            at: Instant::now(),
+        };
+        if let Some(watchers) = pins.watchers.get_mut(&pin) {
+            watchers.retain(|watcher| watcher.send(edge).is_ok());
+        }
+    }
+
+    /// Returns the level last driven on a simulated output pin
+    #[cfg(test)]
+    pub fn output_level(&self, pin: u8) -> Option<bool> {
+        self.pins.lock().unwrap().outputs.get(&pin).copied()
+    }
+
+    /// Listens on a Unix socket for "<pin> <high|low>" lines and applies them to the inputs
+    pub async fn serve(self, path: &str) -> Result<()> {
+        // A stale socket from a previous run would make the bind fail
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Couldn't bind GPIO simulator socket {}", path))?;
+        info!("GPIO simulator listening on {:?}", path);
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let gpio = self.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stream).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    match parse_command(&line) {
+                        Ok((pin, high)) => gpio.set_input(pin, high),
+                        Err(err) => warn!("Ignoring GPIO simulator command {:?}; {}", line, err),
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn parse_command(line: &str) -> Result<(u8, bool)> {
+    let mut parts = line.split_whitespace();
+    let pin = parts
+        .next()
+        .ok_or(Error::msg("Missing pin"))?
+        .parse()
+        .context("Invalid pin")?;
+    let high = match parts.next() {
+        Some("high") | Some("1") => true,
+        Some("low") | Some("0") => false,
+        _ => return Err(Error::msg("Expected level to be high or low")),
+    };
+    Ok((pin, high))
+}
+
+impl GpioInterface for Gpio {
+    fn watch(&mut self, pin: u8, edges: UnboundedSender<Edge>) -> Result<()> {
+        let mut pins = self.pins.lock().unwrap();
+        pins.inputs.entry(pin).or_insert(true);
+        pins.watchers.entry(pin).or_default().push(edges);
+        Ok(())
+    }
+
+    fn output(&mut self, pin: u8) -> Result<Box<dyn OutputLine + Send>> {
+        self.pins.lock().unwrap().outputs.insert(pin, false);
+        Ok(Box::new(SimulatedOutput {
+            pin,
+            pins: self.pins.clone(),
+        }))
+    }
+}
+
+struct SimulatedOutput {
+    pin: u8,
+    pins: Arc<Mutex<Pins>>,
+}
+
+impl OutputLine for SimulatedOutput {
+    fn set_high(&mut self) {
+        self.pins.lock().unwrap().outputs.insert(self.pin, true);
+    }
+
+    fn set_low(&mut self) {
+        self.pins.lock().unwrap().outputs.insert(self.pin, false);
+    }
+}
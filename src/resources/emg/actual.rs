@@ -1,39 +1,74 @@
 // All tasks operating on the EMG system live in this file
 use crate::config::Config;
+use crate::config::EmgConfig;
 use crate::resources::common::Adc;
+use crate::resources::common::adc;
 use anyhow::{Error, Result};
 use log::*;
 
 use crate::resources::Resource;
 use crate::sgcp;
+use crate::status;
 use std::{io, thread, time::Duration};
 
-use rppal::gpio::{Gpio, OutputPin};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+/// An enabled electrode along with its calibrated activation threshold
+pub struct Electrode {
+    pub channel: u8,
+    pub reference_channel: Option<u8>,
+    pub label: String,
+    pub gain: f32,
+    pub threshold: u16,
+    /// Moving average of the electrode's readings in millivolts
+    pub envelope: f32,
+}
 
 pub struct Emg {
-    pub adc: Adc,
+    pub adc: Box<dyn Adc + Send>,
     pub buffer_size: usize,
-    pub inner_threshold: u16,
-    pub outer_threshold: u16,
-    pub inter_channel_sample_duration: u64, // different from sampling speed, this is the time between reading the inner and outer channels
+    pub electrodes: Vec<Electrode>,
+    pub envelope_smoothing: f32,
+    pub inter_channel_sample_duration: u64, // different from sampling speed, this is the time between calibrating consecutive electrodes
 }
 
 impl Resource for Emg {
     fn init() -> Self {
-        let emg_config = Config::global()
+        // A manager restarted after a reload picks up the tuned settings
+        let config = Config::current();
+        let emg_config = config
             .dispatcher
             .emg
             .as_ref()
             .expect("Expected emg config to be defined");
 
-        let adc = Adc::init(emg_config.cs_pin);
+        let adc = adc::init(&emg_config.adc, emg_config.cs_pin);
+
+        // Thresholds are kept with the last calibration rather than here, so an EMG manager
+        // restarted by the supervisor carries on with them instead of starting uncalibrated
+        let calibration = status::calibration();
+        let electrodes = emg_config
+            .enabled_channels()
+            .map(|electrode| Electrode {
+                channel: electrode.channel,
+                reference_channel: electrode.reference_channel,
+                label: electrode.label.clone(),
+                gain: electrode.gain,
+                threshold: calibration
+                    .iter()
+                    .flat_map(|profile| &profile.electrodes)
+                    .find(|calibrated| {
+                        calibrated.channel == electrode.channel
+                            && calibrated.label == electrode.label
+                    })
+                    .map_or(0, |calibrated| calibrated.threshold),
+                envelope: 0.0,
+            })
+            .collect();
 
         Emg {
             adc,
             buffer_size: emg_config.buffer_size,
-            inner_threshold: 0,
-            outer_threshold: 0,
+            electrodes,
+            envelope_smoothing: emg_config.envelope_smoothing,
             inter_channel_sample_duration: emg_config.pause_duration_ms,
         }
     }
@@ -44,55 +79,87 @@ impl Resource for Emg {
 }
 
 impl Emg {
-    pub fn process_data(&self, values: Vec<u16>) -> Result<i32> {
-        if values.len() != 2 {
-            return Err(Error::msg("Expected 2 EMG values"));
+    /// Applies the runtime tunable settings from `emg_config`. Channels can't be changed at runtime,
+    /// so the enabled channels line up with the existing electrodes.
+    pub fn reload(&mut self, emg_config: &EmgConfig) {
+        self.buffer_size = emg_config.buffer_size;
+        self.envelope_smoothing = emg_config.envelope_smoothing;
+        self.inter_channel_sample_duration = emg_config.pause_duration_ms;
+        for (electrode, channel_config) in self
+            .electrodes
+            .iter_mut()
+            .zip(emg_config.enabled_channels())
+        {
+            electrode.gain = channel_config.gain;
         }
+    }
 
-        if values[0] >= self.inner_threshold && values[1] <= self.outer_threshold {
-            Ok(1) // Open
-        } else if values[0] <= self.inner_threshold && values[1] >= self.outer_threshold {
-            Ok(0) // Close
-        } else {
-            Ok(-1) // No action
+    /// Returns the index of the electrode that is the only one at or above its threshold, or `None`
+    /// if no single electrode is active
+    pub fn process_data(&self, values: &[u16]) -> Result<Option<usize>> {
+        if values.len() != self.electrodes.len() {
+            return Err(Error::msg(format!(
+                "Expected {} EMG values, got {}",
+                self.electrodes.len(),
+                values.len()
+            )));
         }
+
+        // Electrode `i` is active when it is at or above its threshold while every other electrode
+        // is at or below its own
+        let is_only_active = |i: usize| {
+            self.electrodes
+                .iter()
+                .zip(values)
+                .enumerate()
+                .all(|(j, (electrode, &value))| {
+                    if i == j {
+                        value >= electrode.threshold
+                    } else {
+                        value <= electrode.threshold
+                    }
+                })
+        };
+
+        Ok((0..values.len()).find(|&i| is_only_active(i)))
     }
 
     pub fn calibrate_emg(&mut self) -> Result<()> {
-        let inner_buffer = self.read_samples(0, "inner");
-
-        info!(
-            "\nFinished inner sampling. Press ENTER when you're ready to start outer sampling..."
-        );
-
-        let _ = io::stdin().read_line(&mut String::new());
-
-        let outer_buffer = self.read_samples(1, "outer");
-
-        let avg_inner = Adc::average_values(inner_buffer.as_ref()).unwrap_or_else(|e| {
-            info!("Error calculating average for inner buffer: {}", e);
-            0
-        });
-
-        let avg_outer = Adc::average_values(outer_buffer.as_ref()).unwrap_or_else(|e| {
-            info!("Error calculating average for outer buffer: {}", e);
-            0
-        });
+        for i in 0..self.electrodes.len() {
+            if i > 0 {
+                info!(
+                    "\nFinished {} sampling. Press ENTER when you're ready to start {} sampling...",
+                    self.electrodes[i - 1].label,
+                    self.electrodes[i].label
+                );
+                let _ = io::stdin().read_line(&mut String::new());
+            }
 
-        self.inner_threshold = avg_inner;
-        self.outer_threshold = avg_outer;
+            let buffer = self.read_samples(i);
+            let label = &self.electrodes[i].label;
+            let threshold = adc::average_values(&buffer).unwrap_or_else(|e| {
+                info!("Error calculating average for {} buffer: {}", label, e);
+                0
+            });
+            self.electrodes[i].threshold = threshold;
+        }
 
         Ok(())
     }
 
-    pub fn read_samples(&mut self, channel: u8, label: &str) -> Vec<u16> {
+    /// Collects `buffer_size` gain-adjusted samples from the electrode at the given index
+    pub fn read_samples(&mut self, index: usize) -> Vec<u16> {
         let mut buffer = Vec::with_capacity(self.buffer_size);
+        let label = self.electrodes[index].label.clone();
         info!("Flex {label}");
 
         while buffer.len() < self.buffer_size {
-            match self.adc.read_channel(channel) {
+            match self.read_electrode(index) {
                 Ok(value) => buffer.push(value),
-                Err(_) => info!("Error reading SPI on channel {channel} during {label}"),
+                Err(_) => info!(
+                    "Error reading SPI on channel {} during {label}",
+                    self.electrodes[index].channel
+                ),
             }
             thread::sleep(Duration::from_millis(self.inter_channel_sample_duration));
         }
@@ -100,7 +167,54 @@ impl Emg {
         buffer
     }
 
-    pub fn read_adc_channels(&mut self, channels: &[u8]) -> Result<Vec<u16>> {
-        self.adc.read_channels(channels)
+    /// Reads the electrode at the given index once, returning the gain-adjusted value
+    pub fn read_electrode(&mut self, index: usize) -> Result<u16> {
+        let electrode = &self.electrodes[index];
+        let value = match electrode.reference_channel {
+            Some(reference) => self.adc.read_differential(electrode.channel, reference)?,
+            None => self.adc.read_channel(electrode.channel)?,
+        };
+        Ok(apply_gain(value, electrode.gain))
     }
+
+    /// Reads every enabled electrode once, returning gain-adjusted values in electrode order
+    pub fn read_electrodes(&mut self) -> Result<Vec<u16>> {
+        if self
+            .electrodes
+            .iter()
+            .any(|e| e.reference_channel.is_some())
+        {
+            return (0..self.electrodes.len())
+                .map(|i| self.read_electrode(i))
+                .collect();
+        }
+
+        // All electrodes are single-ended, so they can be sampled in one batch
+        let channels: Vec<u8> = self.electrodes.iter().map(|e| e.channel).collect();
+        let values = self.adc.read_channels(&channels)?;
+        Ok(self
+            .electrodes
+            .iter()
+            .zip(values)
+            .map(|(electrode, value)| apply_gain(value, electrode.gain))
+            .collect())
+    }
+
+    /// Converts a gain-adjusted electrode reading to millivolts on the ADC's scale. Unless the
+    /// electrode's gain is 1 this is the input voltage scaled by the gain, not the voltage itself.
+    pub fn to_millivolts(&self, value: u16) -> f32 {
+        self.adc.to_millivolts(value)
+    }
+
+    /// Folds one reading per electrode, in millivolts, into the electrode envelopes
+    pub fn update_envelopes(&mut self, millivolts: &[f32]) {
+        let alpha = self.envelope_smoothing;
+        for (electrode, &value) in self.electrodes.iter_mut().zip(millivolts) {
+            electrode.envelope = alpha * value + (1.0 - alpha) * electrode.envelope;
+        }
+    }
+}
+
+fn apply_gain(value: u16, gain: f32) -> u16 {
+    (value as f32 * gain).round().clamp(0.0, u16::MAX as f32) as u16
 }
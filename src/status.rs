@@ -0,0 +1,294 @@
+// Tracks the overall state of the arm and drives the LEDs and buzzers that indicate it locally
+use crate::config::IndicatorConfig;
+use crate::config::IndicatorPattern;
+use crate::config::StatusConfig;
+use crate::journal;
+use crate::journal::Event;
+use crate::resources::common::gpio::GpioInterface;
+use crate::resources::common::gpio::OutputLine;
+use chrono::DateTime;
+use chrono::Utc;
+use log::*;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemState {
+    Boot,
+    Calibrating,
+    Ready,
+    Fault,
+    LowBattery,
+}
+
+/// Why the arm is in `Fault`. It stays there until every cause has been cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaultCause {
+    Estop,
+    #[cfg(feature = "pi")]
+    Calibration,
+    Dispatcher,
+}
+
+struct Status {
+    /// State to show whenever no fault is raised
+    state: SystemState,
+    faults: HashSet<FaultCause>,
+    /// Shown over `state` while no fault is raised
+    low_battery: bool,
+}
+
+static STATUS: LazyLock<Mutex<Status>> = LazyLock::new(|| {
+    Mutex::new(Status {
+        state: SystemState::Boot,
+        faults: HashSet::new(),
+        low_battery: false,
+    })
+});
+
+/// State shown by the indicators
+static SYSTEM_STATE: LazyLock<watch::Sender<SystemState>> =
+    LazyLock::new(|| watch::channel(SystemState::Boot).0);
+
+/// Updates the state of the arm, returning the state it replaces. While a fault is raised the
+/// indicators keep showing `Fault`, and the new state only shows once every fault is cleared.
+pub fn set_state(state: SystemState) -> SystemState {
+    update(|status| std::mem::replace(&mut status.state, state))
+}
+
+/// Puts the arm in `Fault` until `cause` is cleared
+pub fn raise_fault(cause: FaultCause) {
+    update(|status| status.faults.insert(cause));
+}
+
+/// Clears the fault raised by `cause`, leaving `Fault` unless another cause remains
+pub fn clear_fault(cause: FaultCause) {
+    update(|status| status.faults.remove(&cause));
+}
+
+/// Shows `LowBattery` until the battery recovers, returning whether it was low before
+pub fn set_low_battery(low: bool) -> bool {
+    update(|status| std::mem::replace(&mut status.low_battery, low))
+}
+
+pub fn low_battery() -> bool {
+    STATUS.lock().unwrap().low_battery
+}
+
+fn update<T>(f: impl FnOnce(&mut Status) -> T) -> T {
+    let mut status = STATUS.lock().unwrap();
+    let result = f(&mut status);
+    let state = if !status.faults.is_empty() {
+        SystemState::Fault
+    } else if status.low_battery {
+        SystemState::LowBattery
+    } else {
+        status.state
+    };
+    // Only wakes the indicators when the state shown changes, so a pattern isn't restarted
+    SYSTEM_STATE.send_if_modified(|shown| {
+        if *shown == state {
+            return false;
+        }
+        info!("System state changed to {:?}", state);
+        journal::record(Event::StateChanged {
+            from: *shown,
+            to: state,
+        });
+        *shown = state;
+        true
+    });
+    result
+}
+
+pub fn state() -> SystemState {
+    *SYSTEM_STATE.borrow()
+}
+
+/// Activation threshold of an electrode found by the last EMG calibration
+#[derive(Debug, Serialize, Clone)]
+pub struct ElectrodeCalibration {
+    pub label: String,
+    pub channel: u8,
+    pub threshold: u16,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CalibrationProfile {
+    pub electrodes: Vec<ElectrodeCalibration>,
+    pub calibrated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Grip {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct GripState {
+    pub grip: Grip,
+    pub changed_at: DateTime<Utc>,
+}
+
+static CALIBRATION: Mutex<Option<CalibrationProfile>> = Mutex::new(None);
+static LAST_GRIP: Mutex<Option<GripState>> = Mutex::new(None);
+
+/// Records the result of a successful EMG calibration
+#[cfg(feature = "pi")]
+pub fn set_calibration(electrodes: Vec<ElectrodeCalibration>) {
+    journal::record(Event::CalibrationChanged {
+        electrodes: electrodes.clone(),
+    });
+    *CALIBRATION.lock().unwrap() = Some(CalibrationProfile {
+        electrodes,
+        calibrated_at: Utc::now(),
+    });
+}
+
+pub fn calibration() -> Option<CalibrationProfile> {
+    CALIBRATION.lock().unwrap().clone()
+}
+
+/// Records the grip the Maestro was last driven to
+pub fn set_grip(grip: Grip) {
+    let mut last_grip = LAST_GRIP.lock().unwrap();
+    if last_grip.is_none_or(|last| last.grip != grip) {
+        journal::record(Event::GripChanged { grip });
+    }
+    *last_grip = Some(GripState {
+        grip,
+        changed_at: Utc::now(),
+    });
+}
+
+pub fn last_grip() -> Option<GripState> {
+    *LAST_GRIP.lock().unwrap()
+}
+
+/// Drives every configured indicator until the process exits
+pub async fn run_indicators(config: &'static StatusConfig, mut gpio: impl GpioInterface) {
+    let mut indicators = JoinSet::new();
+    for indicator in &config.indicators {
+        match gpio.output(indicator.pin) {
+            Ok(line) => {
+                info!(
+                    "Driving {:?} status indicator on pin {:?}",
+                    indicator.name, indicator.pin
+                );
+                indicators.spawn(run_indicator(indicator, line));
+            },
+            Err(err) => error!(
+                "Failed to set up {:?} status indicator; error={:?}",
+                indicator.name, err
+            ),
+        }
+    }
+    while indicators.join_next().await.is_some() {}
+}
+
+/// Plays the pattern of the current state, restarting whenever the state changes
+async fn run_indicator(indicator: &'static IndicatorConfig, mut line: Box<dyn OutputLine + Send>) {
+    let mut state_rx = SYSTEM_STATE.subscribe();
+    loop {
+        let pattern = indicator.patterns.get(&*state_rx.borrow_and_update());
+        tokio::select! {
+            _ = play(pattern, line.as_mut()) => {
+                // Pattern finished, hold until the next state change
+                if state_rx.changed().await.is_err() {
+                    break;
+                }
+            },
+            changed = state_rx.changed() => if changed.is_err() {
+                break;
+            },
+        }
+    }
+}
+
+async fn play(pattern: Option<&IndicatorPattern>, line: &mut (dyn OutputLine + Send)) {
+    let Some(pattern) = pattern else {
+        line.set_low();
+        return;
+    };
+    loop {
+        for (i, &duration) in pattern.steps_ms.iter().enumerate() {
+            if i % 2 == 0 {
+                line.set_high();
+            } else {
+                line.set_low();
+            }
+            sleep(Duration::from_millis(duration)).await;
+        }
+        if !pattern.repeat || pattern.steps_ms.is_empty() {
+            break;
+        }
+    }
+    line.set_low();
+}
+
+#[cfg(all(test, not(feature = "pi")))]
+mod tests {
+    use super::*;
+    use crate::resources::common::gpio::Gpio;
+
+    #[tokio::test(start_paused = true)]
+    async fn indicator_follows_the_system_state() {
+        let config: StatusConfig = toml::from_str(
+            r#"
+            [[indicators]]
+            name = "status_led"
+            pin = 27
+            [indicators.patterns]
+            ready = { steps_ms = [1000] }
+            fault = { steps_ms = [20, 1000], repeat = false }
+            "#,
+        )
+        .unwrap();
+        let gpio = Gpio::default();
+        tokio::spawn(run_indicators(Box::leak(Box::new(config)), gpio.clone()));
+
+        set_state(SystemState::Ready);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(gpio.output_level(27), Some(true));
+
+        // States without a pattern turn the indicator off
+        set_state(SystemState::Calibrating);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(gpio.output_level(27), Some(false));
+
+        raise_fault(FaultCause::Estop);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(gpio.output_level(27), Some(true));
+        sleep(Duration::from_millis(20)).await;
+        assert_eq!(gpio.output_level(27), Some(false));
+
+        // The fault keeps showing until it is cleared, then the latest state shows
+        set_state(SystemState::Ready);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(state(), SystemState::Fault);
+        assert_eq!(gpio.output_level(27), Some(false));
+        clear_fault(FaultCause::Estop);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(gpio.output_level(27), Some(true));
+
+        // Low battery shows over the state, but not over a fault
+        set_low_battery(true);
+        assert_eq!(state(), SystemState::LowBattery);
+        raise_fault(FaultCause::Estop);
+        assert_eq!(state(), SystemState::Fault);
+        clear_fault(FaultCause::Estop);
+        assert_eq!(state(), SystemState::LowBattery);
+        set_low_battery(false);
+        assert_eq!(state(), SystemState::Ready);
+    }
+}
@@ -0,0 +1,518 @@
+// Pushes metric snapshots to a remote collector for when the arm is offline and can't be scraped.
+// Every push interval a snapshot of the registry is pushed, pushgateway style. Snapshots the
+// collector doesn't accept are kept in a bounded on-disk spool and pushed oldest first once it is
+// back. The pushgateway rejects sample timestamps, so each snapshot carries the time it was
+// captured in `gpm_snapshot_timestamp_seconds` instead. On shutdown a final snapshot is spooled
+// and pushed, see `flush`. Only plain HTTP collectors are supported.
+use crate::config::PushConfig;
+use anyhow::Error;
+use anyhow::Result;
+use http_body_util::Full;
+use hyper::Request;
+use hyper::Uri;
+use hyper::body::Bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use log::*;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tokio::time::MissedTickBehavior;
+use tokio::time::timeout;
+
+const SNAPSHOT_EXTENSION: &str = "prom";
+/// The pushgateway takes the Prometheus text format, not OpenMetrics
+const TEXT_FORMAT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+const SNAPSHOT_TIMESTAMP_METRIC: &str = "gpm_snapshot_timestamp_seconds";
+
+/// Asks the push loop for a final snapshot, answered once it is delivered or spooled
+static FLUSH: OnceLock<mpsc::UnboundedSender<oneshot::Sender<()>>> = OnceLock::new();
+
+/// Directory of metric snapshots waiting to be pushed, named so that they sort oldest first
+pub struct Spool {
+    dir: PathBuf,
+    max_bytes: u64,
+    next_seq: AtomicU64,
+}
+
+impl Spool {
+    pub async fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| Error::msg(format!("Failed to create spool {:?}: {}", dir, e)))?;
+        Ok(Spool {
+            dir,
+            max_bytes,
+            next_seq: AtomicU64::new(0),
+        })
+    }
+
+    /// Adds a snapshot, evicting the oldest ones if the spool grows past `max_bytes`
+    pub async fn append(&self, snapshot: &str) -> Result<()> {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let path = self
+            .dir
+            .join(format!("{:016}-{:06}.{}", millis, seq, SNAPSHOT_EXTENSION));
+
+        // Written under a temporary name so a crash never leaves a partial snapshot behind
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, snapshot).await?;
+        fs::rename(&tmp_path, &path).await?;
+
+        let mut entries = self.entries().await?;
+        let mut total: u64 = entries.iter().map(|(_, size)| size).sum();
+        let mut evicted = 0;
+        while total > self.max_bytes && entries.len() > 1 {
+            let (oldest, size) = entries.remove(0);
+            fs::remove_file(&oldest).await?;
+            total -= size;
+            evicted += 1;
+        }
+        if evicted > 0 {
+            warn!(
+                "Metrics spool is full; dropped {} oldest snapshots",
+                evicted
+            );
+        }
+        Ok(())
+    }
+
+    /// Snapshot files and their sizes, oldest first
+    async fn entries(&self) -> Result<Vec<(PathBuf, u64)>> {
+        let mut entries = Vec::new();
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == SNAPSHOT_EXTENSION)
+            {
+                entries.push((path, entry.metadata().await?.len()));
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    #[cfg(test)]
+    async fn len(&self) -> Result<usize> {
+        Ok(self.entries().await?.len())
+    }
+}
+
+pub struct Pusher {
+    client: Client<HttpConnector, Full<Bytes>>,
+    uri: Uri,
+    request_timeout: Duration,
+    spool: Spool,
+}
+
+impl Pusher {
+    pub fn new(url: &str, request_timeout: Duration, spool: Spool) -> Result<Self> {
+        let uri: Uri = url
+            .parse()
+            .map_err(|e| Error::msg(format!("Invalid push url {:?}: {}", url, e)))?;
+        if uri.scheme_str() != Some("http") {
+            return Err(Error::msg(format!(
+                "Push url {:?} must use http, https is not supported",
+                url
+            )));
+        }
+        Ok(Pusher {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            uri,
+            request_timeout,
+            spool,
+        })
+    }
+
+    /// Pushes a snapshot after any spooled before it, spooling it instead if the collector
+    /// doesn't accept it. Returns the number of snapshots delivered.
+    pub async fn deliver(&self, snapshot: String) -> Result<usize> {
+        let spooled = self.spool.entries().await?.len();
+        let delivered = self.drain().await?;
+        if delivered < spooled {
+            self.spool.append(&snapshot).await?;
+            return Ok(delivered);
+        }
+        match self.push(Bytes::from(snapshot.clone())).await {
+            Ok(_) => Ok(delivered + 1),
+            Err(err) => {
+                debug!(
+                    "Metrics push failed, spooling the snapshot; error={:?}",
+                    err
+                );
+                self.spool.append(&snapshot).await?;
+                Ok(delivered)
+            },
+        }
+    }
+
+    /// Spools a snapshot before pushing everything spooled, so it is kept even if the push is cut
+    /// short. Returns the number of snapshots delivered.
+    pub async fn flush(&self, snapshot: String) -> Result<usize> {
+        self.spool.append(&snapshot).await?;
+        self.drain().await
+    }
+
+    /// Pushes spooled snapshots oldest first, stopping at the first one the collector doesn't
+    /// accept. Returns the number of snapshots delivered.
+    pub async fn drain(&self) -> Result<usize> {
+        let mut delivered = 0;
+        for (path, _) in self.spool.entries().await? {
+            let snapshot = fs::read(&path).await?;
+            if let Err(err) = self.push(Bytes::from(snapshot)).await {
+                debug!(
+                    "Metrics push failed, keeping snapshots spooled; error={:?}",
+                    err
+                );
+                break;
+            }
+            fs::remove_file(&path).await?;
+            delivered += 1;
+        }
+        Ok(delivered)
+    }
+
+    async fn push(&self, snapshot: Bytes) -> Result<()> {
+        let request = Request::post(self.uri.clone())
+            .header(CONTENT_TYPE, TEXT_FORMAT_CONTENT_TYPE)
+            .body(Full::new(snapshot))?;
+        let response = timeout(self.request_timeout, self.client.request(request))
+            .await
+            .map_err(|_| Error::msg("Timed out pushing metrics"))??;
+        if !response.status().is_success() {
+            return Err(Error::msg(format!(
+                "Collector responded with {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Encodes every metric in the registry in the Prometheus text format, followed by the time the
+/// snapshot was taken
+fn snapshot(registry: &Registry, taken_at: SystemTime) -> Result<String> {
+    let mut buffer = String::new();
+    encode(&mut buffer, registry)?;
+
+    // OpenMetrics names counter families without the `_total` their samples carry, the text
+    // format names them after the samples
+    let counters: HashSet<&str> = buffer
+        .lines()
+        .filter_map(|line| line.strip_prefix("# TYPE ")?.strip_suffix(" counter"))
+        .collect();
+    let mut snapshot = String::with_capacity(buffer.len());
+    for line in buffer.lines() {
+        if line == "# EOF" || line.starts_with("# UNIT ") {
+            continue;
+        }
+        let renamed = ["# HELP ", "# TYPE "].iter().find_map(|prefix| {
+            let (name, rest) = line.strip_prefix(prefix)?.split_once(' ')?;
+            counters
+                .contains(name)
+                .then(|| format!("{}{}_total {}", prefix, name, rest))
+        });
+        snapshot.push_str(renamed.as_deref().unwrap_or(line));
+        snapshot.push('\n');
+    }
+
+    let since_epoch = taken_at.duration_since(UNIX_EPOCH)?;
+    snapshot.push_str(&format!(
+        "# HELP {name} Time the snapshot was taken.\n\
+         # TYPE {name} gauge\n\
+         {name} {}.{:03}\n",
+        since_epoch.as_secs(),
+        since_epoch.subsec_millis(),
+        name = SNAPSHOT_TIMESTAMP_METRIC,
+    ));
+    Ok(snapshot)
+}
+
+/// Spools a final snapshot and pushes whatever is spooled. Returns right away when metrics aren't
+/// pushed.
+pub async fn flush() {
+    let Some(flush_tx) = FLUSH.get() else {
+        return;
+    };
+    let (done_tx, done_rx) = oneshot::channel();
+    if flush_tx.send(done_tx).is_ok() {
+        let _ = done_rx.await;
+    }
+}
+
+/// Snapshots and pushes the registry every `interval_in_seconds`, forever
+pub async fn run(config: &'static PushConfig, registry: Arc<Registry>) {
+    let pusher = match Spool::open(&config.spool_dir, config.max_spool_bytes)
+        .await
+        .and_then(|spool| {
+            Pusher::new(
+                &config.url,
+                Duration::from_millis(config.request_timeout_ms),
+                spool,
+            )
+        }) {
+        Ok(pusher) => pusher,
+        Err(err) => {
+            error!("Failed to start metrics push; error={:?}", err);
+            return;
+        },
+    };
+    info!(
+        "Pushing metrics to {:?} every {}s",
+        config.url, config.interval_in_seconds
+    );
+
+    let (flush_tx, mut flush_rx) = mpsc::unbounded_channel::<oneshot::Sender<()>>();
+    let _ = FLUSH.set(flush_tx);
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(config.interval_in_seconds.max(1)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let done = tokio::select! {
+            _ = interval.tick() => None,
+            Some(done) = flush_rx.recv() => Some(done),
+        };
+        let snapshot = match snapshot(&registry, SystemTime::now()) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                error!("Failed to encode metrics snapshot; error={:?}", err);
+                continue;
+            },
+        };
+        let delivered = match done {
+            Some(_) => pusher.flush(snapshot).await,
+            None => pusher.deliver(snapshot).await,
+        };
+        match delivered {
+            Ok(delivered) if delivered > 0 => debug!("Pushed {} metrics snapshots", delivered),
+            Ok(_) => (),
+            Err(err) => error!("Failed to spool metrics snapshot; error={:?}", err),
+        }
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use hyper::Response;
+    use hyper::StatusCode;
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicBool;
+    use tokio::net::TcpListener;
+
+    /// Stand-in collector recording every snapshot it accepts
+    #[derive(Clone, Default)]
+    struct Collector {
+        received: Arc<Mutex<Vec<String>>>,
+        content_types: Arc<Mutex<Vec<String>>>,
+        offline: Arc<AtomicBool>,
+    }
+
+    impl Collector {
+        async fn serve(&self) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/metrics/job/gpm", listener.local_addr().unwrap());
+            let collector = self.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let collector = collector.clone();
+                    tokio::spawn(async move {
+                        let service = service_fn(|req: Request<Incoming>| {
+                            let collector = collector.clone();
+                            async move {
+                                let content_type = req.headers()[CONTENT_TYPE].to_str().unwrap();
+                                collector
+                                    .content_types
+                                    .lock()
+                                    .unwrap()
+                                    .push(content_type.to_string());
+                                let body = req.into_body().collect().await.unwrap().to_bytes();
+                                let status = if collector.offline.load(Ordering::SeqCst) {
+                                    StatusCode::SERVICE_UNAVAILABLE
+                                } else {
+                                    collector
+                                        .received
+                                        .lock()
+                                        .unwrap()
+                                        .push(String::from_utf8(body.to_vec()).unwrap());
+                                    StatusCode::OK
+                                };
+                                let mut response = Response::new(Full::new(Bytes::new()));
+                                *response.status_mut() = status;
+                                Ok::<_, Infallible>(response)
+                            }
+                        });
+                        let _ = http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await;
+                    });
+                }
+            });
+            url
+        }
+    }
+
+    async fn spool(name: &str, max_bytes: u64) -> Spool {
+        let dir = std::env::temp_dir().join(format!("gpm-spool-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        Spool::open(dir, max_bytes).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_spooled_while_offline_and_pushed_in_order() {
+        let collector = Collector::default();
+        let url = collector.serve().await;
+        let pusher =
+            Pusher::new(&url, Duration::from_secs(1), spool("order", 1 << 20).await).unwrap();
+
+        // Pushed straight away while the collector is up
+        assert_eq!(pusher.deliver("first".to_string()).await.unwrap(), 1);
+        assert_eq!(pusher.spool.len().await.unwrap(), 0);
+
+        collector.offline.store(true, Ordering::SeqCst);
+        for snapshot in ["second", "third"] {
+            assert_eq!(pusher.deliver(snapshot.to_string()).await.unwrap(), 0);
+        }
+        assert_eq!(pusher.spool.len().await.unwrap(), 2);
+
+        collector.offline.store(false, Ordering::SeqCst);
+        assert_eq!(pusher.deliver("fourth".to_string()).await.unwrap(), 3);
+        assert_eq!(pusher.spool.len().await.unwrap(), 0);
+        assert_eq!(
+            *collector.received.lock().unwrap(),
+            vec!["first", "second", "third", "fourth"]
+        );
+        fs::remove_dir_all(&pusher.spool.dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pushes_the_text_format_with_the_capture_time_as_a_gauge() {
+        let collector = Collector::default();
+        let url = collector.serve().await;
+        let pusher =
+            Pusher::new(&url, Duration::from_secs(1), spool("format", 1 << 20).await).unwrap();
+
+        let mut registry = Registry::default();
+        let counter = prometheus_client::metrics::counter::Counter::<u64>::default();
+        counter.inc();
+        registry.register("pushes", "Test counter", counter);
+        let gauge = prometheus_client::metrics::gauge::Gauge::<i64>::default();
+        gauge.set(3);
+        registry.register("queued", "Test gauge", gauge);
+
+        let taken_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        let snapshot = snapshot(&registry, taken_at).unwrap();
+        assert_eq!(pusher.deliver(snapshot).await.unwrap(), 1);
+
+        assert_eq!(
+            *collector.received.lock().unwrap(),
+            vec![
+                "# HELP pushes_total Test counter.\n\
+                 # TYPE pushes_total counter\n\
+                 pushes_total 1\n\
+                 # HELP queued Test gauge.\n\
+                 # TYPE queued gauge\n\
+                 queued 3\n\
+                 # HELP gpm_snapshot_timestamp_seconds Time the snapshot was taken.\n\
+                 # TYPE gpm_snapshot_timestamp_seconds gauge\n\
+                 gpm_snapshot_timestamp_seconds 1700000000.250\n"
+            ]
+        );
+        assert_eq!(
+            *collector.content_types.lock().unwrap(),
+            vec!["text/plain; version=0.0.4; charset=utf-8"]
+        );
+        fs::remove_dir_all(&pusher.spool.dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flushed_snapshots_are_spooled_until_pushed() {
+        let collector = Collector::default();
+        let url = collector.serve().await;
+        let pusher =
+            Pusher::new(&url, Duration::from_secs(1), spool("flush", 1 << 20).await).unwrap();
+
+        collector.offline.store(true, Ordering::SeqCst);
+        assert_eq!(pusher.flush("first".to_string()).await.unwrap(), 0);
+        assert_eq!(pusher.spool.len().await.unwrap(), 1);
+
+        collector.offline.store(false, Ordering::SeqCst);
+        assert_eq!(pusher.flush("second".to_string()).await.unwrap(), 2);
+        assert_eq!(pusher.spool.len().await.unwrap(), 0);
+        assert_eq!(*collector.received.lock().unwrap(), vec!["first", "second"]);
+        fs::remove_dir_all(&pusher.spool.dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unreachable_collector_keeps_snapshots() {
+        // Nothing listens on port 9 (discard) of localhost
+        let pusher = Pusher::new(
+            "http://127.0.0.1:9/metrics/job/gpm",
+            Duration::from_secs(1),
+            spool("unreachable", 1 << 20).await,
+        )
+        .unwrap();
+
+        pusher.spool.append("snapshot").await.unwrap();
+        assert_eq!(pusher.drain().await.unwrap(), 0);
+        assert_eq!(pusher.spool.len().await.unwrap(), 1);
+        fs::remove_dir_all(&pusher.spool.dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn full_spool_drops_the_oldest_snapshots() {
+        let collector = Collector::default();
+        let url = collector.serve().await;
+        // Room for two 10 byte snapshots
+        let pusher = Pusher::new(&url, Duration::from_secs(1), spool("bounded", 25).await).unwrap();
+
+        for snapshot in ["snapshot-1", "snapshot-2", "snapshot-3"] {
+            pusher.spool.append(snapshot).await.unwrap();
+        }
+        assert_eq!(pusher.spool.len().await.unwrap(), 2);
+
+        assert_eq!(pusher.drain().await.unwrap(), 2);
+        assert_eq!(
+            *collector.received.lock().unwrap(),
+            vec!["snapshot-2", "snapshot-3"]
+        );
+        fs::remove_dir_all(&pusher.spool.dir).await.unwrap();
+    }
+
+    #[test]
+    fn rejects_https_collectors() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let spool = runtime.block_on(spool("https", 1 << 20));
+        let dir = spool.dir.clone();
+        assert!(Pusher::new("https://grm.local/metrics", Duration::from_secs(1), spool).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
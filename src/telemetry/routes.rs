@@ -0,0 +1,215 @@
+// Request routing for the telemetry server
+use crate::ManagerChannelMap;
+use crate::config::Config;
+use crate::dispatchers::arbiter;
+use crate::dispatchers::estop;
+use crate::journal;
+use crate::managers::supervisor;
+use crate::status;
+use crate::telemetry::stream;
+use http_body_util::Full;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use hyper::body::Bytes;
+use hyper::body::Incoming;
+use hyper::header::CONTENT_TYPE;
+use log::*;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use serde_json::Value;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+const DEFAULT_JOURNAL_LIMIT: usize = 100;
+const MAX_JOURNAL_LIMIT: usize = 10_000;
+
+pub fn route(
+    req: Request<Incoming>,
+    registry: &Registry,
+    manager_channel_map: &ManagerChannelMap,
+) -> Response<Full<Bytes>> {
+    debug!("Telemetry request {} {}", req.method(), req.uri().path());
+    if req.method() != Method::GET {
+        return text(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+    }
+
+    match req.uri().path() {
+        "/stream" => {
+            stream::upgrade(req).unwrap_or_else(|(status, message)| text(status, &message))
+        },
+        "/metrics" => metrics(registry),
+        "/healthz" => text(StatusCode::OK, "ok"),
+        "/readyz" => readiness(manager_channel_map),
+        "/status" => system_status(manager_channel_map),
+        "/journal" => journal_entries(&req),
+        _ => text(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+fn metrics(registry: &Registry) -> Response<Full<Bytes>> {
+    let mut buffer = String::new();
+    match encode(&mut buffer, registry) {
+        Ok(_) => respond(StatusCode::OK, OPENMETRICS_CONTENT_TYPE, buffer),
+        Err(err) => {
+            error!("Failed to encode metrics; error={:?}", err);
+            text(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to encode metrics",
+            )
+        },
+    }
+}
+
+/// Ready once every resource manager is running
+fn readiness(manager_channel_map: &ManagerChannelMap) -> Response<Full<Bytes>> {
+    let managers = manager_readiness(manager_channel_map);
+    let ready = managers.values().all(|&ready| ready);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    json_response(status, &json!({ "ready": ready, "managers": managers }))
+}
+
+fn system_status(manager_channel_map: &ManagerChannelMap) -> Response<Full<Bytes>> {
+    let config = Config::current();
+    let body = json!({
+        "state": status::state(),
+        "estop_engaged": estop::engaged(),
+        "dispatchers": config.command_dispatch_strategy,
+        "motion_override": arbiter::override_holder(),
+        "managers": manager_readiness(manager_channel_map),
+        "calibration": status::calibration(),
+        "last_grip": status::last_grip(),
+        "config": {
+            "tcp_address": config.dispatcher.tcp.address,
+            "http_address": config.dispatcher.http.as_ref().map(|http| &http.address),
+            "telemetry_address": config.telemetry.as_ref().map(|telemetry| &telemetry.address),
+            "arbitration_priority": config.arbitration.priority,
+            "estop_pin": config.estop.pin,
+            "gpio_buttons": config
+                .dispatcher
+                .gpio_monitor
+                .as_ref()
+                .map_or(0, |gpio_monitor| gpio_monitor.buttons.len()),
+        },
+    });
+    json_response(StatusCode::OK, &body)
+}
+
+/// Latest journal entries, oldest first. Defaults to the last 100, `?limit=N` asks for more.
+fn journal_entries(req: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let limit = req
+        .uri()
+        .query()
+        .and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("limit="))
+        })
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_JOURNAL_LIMIT)
+        .min(MAX_JOURNAL_LIMIT);
+
+    match journal::tail(limit) {
+        None => text(StatusCode::NOT_FOUND, "Journal is not configured"),
+        Some(Ok(entries)) => json_response(StatusCode::OK, &json!({ "entries": entries })),
+        Some(Err(err)) => {
+            error!("Failed to read journal; error={:?}", err);
+            text(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read journal")
+        },
+    }
+}
+
+fn manager_readiness(manager_channel_map: &ManagerChannelMap) -> BTreeMap<&str, bool> {
+    manager_channel_map
+        .keys()
+        .map(|resource| (resource.as_str(), supervisor::is_running(resource)))
+        .collect()
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Full<Bytes>> {
+    respond(status, JSON_CONTENT_TYPE, body.to_string())
+}
+
+fn text(status: StatusCode, body: &str) -> Response<Full<Bytes>> {
+    respond(status, TEXT_CONTENT_TYPE, body.to_string())
+}
+
+fn respond(status: StatusCode, content_type: &str, body: String) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::manager_queue;
+    use http_body_util::BodyExt;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    async fn json_body(res: Response<Full<Bytes>>) -> Value {
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn ready_once_every_manager_runs() {
+        let config = &Config::global().supervisor;
+        tokio::spawn(supervisor::supervise("ROUTES_TEST", config, || {
+            tokio::spawn(std::future::pending())
+        }));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut manager_channel_map: ManagerChannelMap =
+            HashMap::from([("ROUTES_TEST".to_string(), manager_queue("ROUTES_TEST"))]);
+        let res = readiness(&manager_channel_map);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            json_body(res).await,
+            json!({ "ready": true, "managers": { "ROUTES_TEST": true } })
+        );
+
+        manager_channel_map.insert("NOT_STARTED".to_string(), manager_queue("NOT_STARTED"));
+        let res = readiness(&manager_channel_map);
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            json_body(res).await,
+            json!({
+                "ready": false,
+                "managers": { "NOT_STARTED": false, "ROUTES_TEST": true },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_the_system_status() {
+        let manager_channel_map: ManagerChannelMap =
+            HashMap::from([("NOT_STARTED".to_string(), manager_queue("NOT_STARTED"))]);
+        let res = system_status(&manager_channel_map);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_TYPE], JSON_CONTENT_TYPE);
+
+        let body = json_body(res).await;
+        let config = Config::current();
+        assert!(body["estop_engaged"].is_boolean());
+        assert!(body["state"].is_string());
+        assert_eq!(body["managers"], json!({ "NOT_STARTED": false }));
+        assert_eq!(
+            body["dispatchers"],
+            serde_json::to_value(&config.command_dispatch_strategy).unwrap()
+        );
+        assert_eq!(body["config"]["tcp_address"], config.dispatcher.tcp.address);
+        assert_eq!(body["config"]["estop_pin"], json!(config.estop.pin));
+    }
+}
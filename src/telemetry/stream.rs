@@ -0,0 +1,308 @@
+// Live stream of the EMG envelopes, classifier output and servo positions over a WebSocket, for
+// watching the arm while training a pilot. Producers overwrite the latest frame without ever
+// waiting on clients. Each client samples that frame at its own rate, so a slow client only sees
+// fewer frames and is disconnected if it stops accepting them altogether.
+use crate::config::Config;
+use crate::config::StreamConfig;
+use crate::status::Grip;
+use futures_util::SinkExt;
+use futures_util::StreamExt;
+use http_body_util::Full;
+use hyper::Request;
+use hyper::Response;
+use hyper::StatusCode;
+use hyper::body::Bytes;
+use hyper::header::CONNECTION;
+use hyper::header::SEC_WEBSOCKET_ACCEPT;
+use hyper::header::SEC_WEBSOCKET_KEY;
+use hyper::header::SEC_WEBSOCKET_VERSION;
+use hyper::header::UPGRADE;
+use hyper_util::rt::TokioIo;
+use log::*;
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+use tokio::time::timeout;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+const WEBSOCKET_VERSION: &str = "13";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ElectrodeEnvelope {
+    pub label: String,
+    pub envelope_mv: f32,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct LiveFrame {
+    pub emg: Vec<ElectrodeEnvelope>,
+    /// Electrode the classifier found active, if exactly one was
+    pub active_electrode: Option<String>,
+    pub classified_grip: Option<Grip>,
+    /// Position of each finger servo in quarter-microseconds
+    pub servo_positions: Vec<u16>,
+}
+
+static LIVE_FRAME: LazyLock<watch::Sender<LiveFrame>> =
+    LazyLock::new(|| watch::channel(LiveFrame::default()).0);
+
+static CLIENTS: LazyLock<Arc<Semaphore>> = LazyLock::new(|| {
+    Arc::new(Semaphore::new(
+        stream_config().map_or(0, |config| config.max_clients),
+    ))
+});
+
+fn stream_config() -> Option<&'static StreamConfig> {
+    Config::global()
+        .telemetry
+        .as_ref()
+        .map(|telemetry| &telemetry.stream)
+}
+
+/// Publishes the latest EMG envelopes along with the classifier's decision
+#[cfg(feature = "pi")]
+pub fn publish_emg(
+    envelopes: Vec<ElectrodeEnvelope>,
+    active_electrode: Option<String>,
+    classified_grip: Grip,
+) {
+    LIVE_FRAME.send_modify(|frame| {
+        frame.emg = envelopes;
+        frame.active_electrode = active_electrode;
+        frame.classified_grip = Some(classified_grip);
+    });
+}
+
+#[cfg(feature = "pi")]
+pub fn publish_servo_positions(positions: Vec<u16>) {
+    LIVE_FRAME.send_modify(|frame| frame.servo_positions = positions);
+}
+
+/// Accepts a WebSocket handshake and streams frames over the upgraded connection. Clients may ask
+/// for a lower rate than the configured one with `?rate_hz=N`.
+pub fn upgrade<B>(mut req: Request<B>) -> Result<Response<Full<Bytes>>, (StatusCode, String)> {
+    let config = stream_config().ok_or((
+        StatusCode::NOT_FOUND,
+        "Live stream is not configured".to_string(),
+    ))?;
+
+    let is_websocket = req
+        .headers()
+        .get(UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let key = req.headers().get(SEC_WEBSOCKET_KEY);
+    let version = req.headers().get(SEC_WEBSOCKET_VERSION);
+    let (true, Some(key), Some(WEBSOCKET_VERSION)) =
+        (is_websocket, key, version.and_then(|v| v.to_str().ok()))
+    else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Expected a WebSocket (version 13) upgrade request".to_string(),
+        ));
+    };
+    let accept_key = derive_accept_key(key.as_bytes());
+
+    let permit = CLIENTS.clone().try_acquire_owned().map_err(|_| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "Already streaming to the maximum of {} clients",
+                config.max_clients
+            ),
+        )
+    })?;
+    let rate_hz = rate_hz(&req, config);
+    let send_timeout = Duration::from_millis(config.send_timeout_ms);
+
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let ws =
+                    WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None)
+                        .await;
+                serve_client(ws, rate_hz, send_timeout, permit).await;
+            },
+            Err(err) => error!("Live stream upgrade failed; error={:?}", err),
+        }
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept_key)
+        .body(Full::new(Bytes::new()))
+        .unwrap())
+}
+
+/// Rate to stream at, the client's `?rate_hz=N` if it is lower than the configured rate
+fn rate_hz<B>(req: &Request<B>, config: &StreamConfig) -> u32 {
+    let requested = req
+        .uri()
+        .query()
+        .and_then(|query| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("rate_hz="))
+        })
+        .and_then(|rate_hz| rate_hz.parse().ok());
+    requested
+        .unwrap_or(config.rate_hz)
+        .clamp(1, config.rate_hz.max(1))
+}
+
+async fn serve_client<S>(
+    ws: WebSocketStream<S>,
+    rate_hz: u32,
+    send_timeout: Duration,
+    _permit: OwnedSemaphorePermit,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    info!("Live stream client connected at {} Hz", rate_hz);
+    let (mut sink, mut source) = ws.split();
+    let mut frame_rx = LIVE_FRAME.subscribe();
+    // Start the client off with the current frame
+    frame_rx.mark_changed();
+    let mut interval = tokio::time::interval(Duration::from_secs(1) / rate_hz);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if !frame_rx.has_changed().unwrap_or(false) {
+                    continue;
+                }
+                let frame = match serde_json::to_string(&*frame_rx.borrow_and_update()) {
+                    Ok(frame) => frame,
+                    Err(err) => {
+                        error!("Failed to encode live frame; error={:?}", err);
+                        continue;
+                    },
+                };
+                match timeout(send_timeout, sink.send(Message::text(frame))).await {
+                    Ok(Ok(_)) => (),
+                    Ok(Err(err)) => {
+                        info!("Live stream client went away; error={:?}", err);
+                        break;
+                    },
+                    Err(_) => {
+                        warn!("Dropping live stream client that stopped reading");
+                        break;
+                    },
+                }
+            },
+            message = source.next() => match message {
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Err(err)) => {
+                    info!("Live stream client went away; error={:?}", err);
+                    break;
+                },
+                // Pings are answered by tungstenite, anything else is ignored
+                Some(Ok(_)) => (),
+            },
+        }
+    }
+    info!("Live stream client disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn answers_the_websocket_handshake() {
+        let handshake = || {
+            Request::get("/stream")
+                .header(UPGRADE, "websocket")
+                .header(CONNECTION, "Upgrade")
+                .header(SEC_WEBSOCKET_VERSION, WEBSOCKET_VERSION)
+        };
+
+        // Key and accept key from the example in RFC 6455
+        let req = handshake()
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap();
+        let res = upgrade(req).unwrap();
+        assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            res.headers()[SEC_WEBSOCKET_ACCEPT],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let req = handshake().body(()).unwrap();
+        let (status, _) = upgrade(req).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn clients_may_only_lower_the_rate() {
+        let config = StreamConfig {
+            rate_hz: 10,
+            ..Default::default()
+        };
+        let rate_hz = |uri: &str| rate_hz(&Request::get(uri).body(()).unwrap(), &config);
+        assert_eq!(rate_hz("/stream"), 10);
+        assert_eq!(rate_hz("/stream?rate_hz=2"), 2);
+        assert_eq!(rate_hz("/stream?rate_hz=50"), 10);
+        assert_eq!(rate_hz("/stream?rate_hz=0"), 1);
+        assert_eq!(rate_hz("/stream?rate_hz=fast"), 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn streams_frames_at_the_client_rate() {
+        let (server, client) = duplex(64 * 1024);
+        let ws = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+        tokio::spawn(serve_client(ws, 5, Duration::from_secs(1), permit));
+
+        // Frames change far more often than the client asked to hear about them
+        tokio::spawn(async {
+            for position in 0.. {
+                LIVE_FRAME.send_modify(|frame| frame.servo_positions = vec![position; 3]);
+                sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let mut client = WebSocketStream::from_raw_socket(client, Role::Client, None).await;
+        let mut frames = 0;
+        let _ = timeout(Duration::from_millis(990), async {
+            while let Some(Ok(_)) = client.next().await {
+                frames += 1;
+            }
+        })
+        .await;
+        assert_eq!(frames, 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_clients_that_stop_reading() {
+        // Too small to take a whole frame, and the client never reads from it
+        let (server, _client) = duplex(16);
+        let ws = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+        let clients = Arc::new(Semaphore::new(1));
+        let permit = clients.clone().try_acquire_owned().unwrap();
+        let served = tokio::spawn(serve_client(ws, 10, Duration::from_millis(500), permit));
+
+        timeout(Duration::from_secs(1), served)
+            .await
+            .expect("client was never dropped")
+            .unwrap();
+        assert_eq!(clients.available_permits(), 1);
+    }
+}
@@ -0,0 +1,181 @@
+// System metrics are refreshed by a background sampler on every telemetry tick so that scrapes
+// only have to encode the latest values
+use super::Label;
+use log::*;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+use std::fs;
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+use sysinfo::Components;
+use sysinfo::CpuRefreshKind;
+use sysinfo::Disks;
+use sysinfo::MemoryRefreshKind;
+use sysinfo::RefreshKind;
+use sysinfo::System;
+use tokio::time::MissedTickBehavior;
+
+type GaugeMetric = Family<Label, Gauge>;
+type FloatGaugeMetric = Family<Label, Gauge<f64, AtomicU64>>;
+
+/// Firmware throttling flags reported by the Pi, see `vcgencmd get_throttled`
+const THROTTLED_PATH: &str = "/sys/devices/platform/soc/soc:firmware/get_throttled";
+const THROTTLE_CONDITIONS: [(u32, &str); 4] = [
+    (0, "under_voltage"),
+    (1, "frequency_capped"),
+    (2, "throttled"),
+    (3, "soft_temperature_limit"),
+];
+/// Offset of the "has occurred since boot" copy of each flag
+const THROTTLE_SINCE_BOOT_OFFSET: u32 = 16;
+
+#[derive(Clone, Default)]
+pub struct SystemMetrics {
+    cpu_usage: GaugeMetric,
+    memory_usage: GaugeMetric,
+    temperature: FloatGaugeMetric,
+    throttling: GaugeMetric,
+    load_average: FloatGaugeMetric,
+    disk_total: GaugeMetric,
+    disk_available: GaugeMetric,
+    uptime: GaugeMetric,
+}
+
+impl SystemMetrics {
+    pub fn register(&self, registry: &mut Registry) {
+        registry.register(
+            "cpu_usage",
+            "Current CPU load percentage",
+            self.cpu_usage.clone(),
+        );
+        registry.register(
+            "memory_usage",
+            "Current memory utilization",
+            self.memory_usage.clone(),
+        );
+        registry.register(
+            "temperature_celsius",
+            "Temperature of each hardware sensor",
+            self.temperature.clone(),
+        );
+        registry.register(
+            "throttling",
+            "Firmware throttling flags, currently active or occurred since boot",
+            self.throttling.clone(),
+        );
+        registry.register(
+            "load_average",
+            "System load average over 1, 5 and 15 minutes",
+            self.load_average.clone(),
+        );
+        registry.register(
+            "disk_total_bytes",
+            "Size of each mounted disk",
+            self.disk_total.clone(),
+        );
+        registry.register(
+            "disk_available_bytes",
+            "Free space on each mounted disk",
+            self.disk_available.clone(),
+        );
+        registry.register(
+            "uptime_seconds",
+            "Time since the system booted",
+            self.uptime.clone(),
+        );
+    }
+
+    /// Refreshes every metric once per `tick`, forever
+    pub async fn sample(self, tick: Duration) {
+        let mut sys = System::new_with_specifics(
+            RefreshKind::new()
+                .with_cpu(CpuRefreshKind::new().with_cpu_usage())
+                .with_memory(MemoryRefreshKind::everything()),
+        );
+        let mut components = Components::new_with_refreshed_list();
+        let mut disks = Disks::new_with_refreshed_list();
+
+        let mut interval = tokio::time::interval(tick);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            // CPU usage is the difference between two refreshes, so the first tick reads 0
+            sys.refresh_cpu_usage();
+            sys.refresh_memory();
+            components.refresh();
+            disks.refresh();
+
+            self.cpu_usage
+                .get_or_create(&vec![])
+                .set(sys.global_cpu_usage() as i64);
+            self.memory_usage
+                .get_or_create(&vec![])
+                .set(sys.used_memory() as i64);
+
+            for component in &components {
+                self.temperature
+                    .get_or_create(&vec![("sensor".to_string(), component.label().to_string())])
+                    .set(component.temperature() as f64);
+            }
+
+            self.sample_throttling();
+
+            let load_average = System::load_average();
+            for (window, load) in [
+                ("1m", load_average.one),
+                ("5m", load_average.five),
+                ("15m", load_average.fifteen),
+            ] {
+                self.load_average
+                    .get_or_create(&vec![("window".to_string(), window.to_string())])
+                    .set(load);
+            }
+
+            for disk in &disks {
+                let labels = vec![(
+                    "mount_point".to_string(),
+                    disk.mount_point().display().to_string(),
+                )];
+                self.disk_total
+                    .get_or_create(&labels)
+                    .set(disk.total_space() as i64);
+                self.disk_available
+                    .get_or_create(&labels)
+                    .set(disk.available_space() as i64);
+            }
+
+            self.uptime
+                .get_or_create(&vec![])
+                .set(System::uptime() as i64);
+        }
+    }
+
+    /// Only available on the Pi, the metric is left empty elsewhere
+    fn sample_throttling(&self) {
+        let Ok(content) = fs::read_to_string(THROTTLED_PATH) else {
+            return;
+        };
+        let flags = match u32::from_str_radix(content.trim().trim_start_matches("0x"), 16) {
+            Ok(flags) => flags,
+            Err(err) => {
+                warn!(
+                    "Failed to parse throttling flags {:?}; error={:?}",
+                    content, err
+                );
+                return;
+            },
+        };
+
+        for (bit, condition) in THROTTLE_CONDITIONS {
+            for (scope, offset) in [("now", 0), ("since_boot", THROTTLE_SINCE_BOOT_OFFSET)] {
+                self.throttling
+                    .get_or_create(&vec![
+                        ("condition".to_string(), condition.to_string()),
+                        ("scope".to_string(), scope.to_string()),
+                    ])
+                    .set(((flags >> (bit + offset)) & 1) as i64);
+            }
+        }
+    }
+}
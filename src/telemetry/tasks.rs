@@ -0,0 +1,136 @@
+// Task metrics are recorded by `dispatch_task` and the resource managers, and registered with the
+// exporter's registry so they are served alongside the system metrics
+use super::Label;
+use crate::dispatchers;
+use anyhow::Result;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::metrics::histogram::exponential_buckets;
+use prometheus_client::registry::Registry;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+type CounterMetric = Family<Label, Counter>;
+type GaugeMetric = Family<Label, Gauge>;
+type HistogramMetric = Family<Label, Histogram, fn() -> Histogram>;
+
+pub static TASK_METRICS: LazyLock<TaskMetrics> = LazyLock::new(TaskMetrics::new);
+
+pub struct TaskMetrics {
+    dispatched: CounterMetric,
+    succeeded: CounterMetric,
+    failed: CounterMetric,
+    timeouts: CounterMetric,
+    latency: HistogramMetric,
+    queue_depth: GaugeMetric,
+    restarts: CounterMetric,
+}
+
+impl TaskMetrics {
+    fn new() -> Self {
+        Self {
+            dispatched: CounterMetric::default(),
+            succeeded: CounterMetric::default(),
+            failed: CounterMetric::default(),
+            timeouts: CounterMetric::default(),
+            // 1ms to ~16s
+            latency: HistogramMetric::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.001, 2.0, 15))
+            }),
+            queue_depth: GaugeMetric::default(),
+            restarts: CounterMetric::default(),
+        }
+    }
+
+    pub fn register(&self, registry: &mut Registry) {
+        registry.register(
+            "tasks_dispatched",
+            "Tasks handed to a resource manager",
+            self.dispatched.clone(),
+        );
+        registry.register(
+            "tasks_succeeded",
+            "Tasks a resource manager completed successfully",
+            self.succeeded.clone(),
+        );
+        registry.register(
+            "tasks_failed",
+            "Tasks that were rejected, lost or failed, by error kind",
+            self.failed.clone(),
+        );
+        registry.register(
+            "task_timeouts",
+            "Tasks the dispatcher stopped waiting for after their deadline",
+            self.timeouts.clone(),
+        );
+        registry.register(
+            "task_latency_seconds",
+            "Time from dispatch to the resource manager's response",
+            self.latency.clone(),
+        );
+        registry.register(
+            "manager_queue_depth",
+            "Tasks waiting in a resource manager's channel",
+            self.queue_depth.clone(),
+        );
+        registry.register(
+            "manager_restarts",
+            "Times the supervisor re-created a resource manager that exited or panicked",
+            self.restarts.clone(),
+        );
+    }
+
+    pub fn record_dispatched(&self, resource: &str, task_code: &str) {
+        self.dispatched
+            .get_or_create(&task_labels(resource, task_code))
+            .inc();
+    }
+
+    /// Records the result of a dispatched task. Errors raised while routing are labelled with
+    /// their `DispatchError` kind, error responses from the resource manager with "task_error".
+    pub fn record_outcome(
+        &self,
+        resource: &str,
+        task_code: &str,
+        elapsed: Duration,
+        result: &Result<String>,
+    ) {
+        let labels = task_labels(resource, task_code);
+        self.latency
+            .get_or_create(&labels)
+            .observe(elapsed.as_secs_f64());
+
+        let Some(kind) = dispatchers::failure_kind(result) else {
+            self.succeeded.get_or_create(&labels).inc();
+            return;
+        };
+
+        if kind == "timeout" {
+            self.timeouts.get_or_create(&labels).inc();
+        }
+        let mut labels = labels;
+        labels.push(("kind".to_string(), kind.to_string()));
+        self.failed.get_or_create(&labels).inc();
+    }
+
+    pub fn record_restart(&self, resource: &str) {
+        self.restarts
+            .get_or_create(&vec![("resource".to_string(), resource.to_string())])
+            .inc();
+    }
+
+    pub fn set_queue_depth(&self, resource: &str, depth: usize) {
+        self.queue_depth
+            .get_or_create(&vec![("resource".to_string(), resource.to_string())])
+            .set(depth as i64);
+    }
+}
+
+fn task_labels(resource: &str, task_code: &str) -> Label {
+    vec![
+        ("resource".to_string(), resource.to_string()),
+        ("task_code".to_string(), task_code.to_string()),
+    ]
+}